@@ -1,119 +1,457 @@
 use crate::{
-    config::Config,
-    file_ops::{clear_directory_safely, create_directory_safely, safely_write_file},
-    images::process_content_images,
+    callouts::setup_callouts,
+    code_blocks::setup_code_blocks,
+    logger::{self, debug, error, info, warn},
+    config::{Config, DEFAULT_CONFIG, LazyMode},
+    external_links::setup_external_links,
+    filters::register_filters,
+    heading_anchors::setup_heading_anchors,
+    file_ops::{STAGING_DIR, clear_directory_safely, create_directory_safely, safely_write_file, static_url, swap_directory},
+    i18n::{Translations, page_language},
+    images::{copy_content_file, is_image, process_content_images, use_webp_urls},
+    incremental::{BuildState, is_up_to_date, prune_orphans},
+    json_ld::{ArticleData, article_json_ld},
     lazy_load::{add_lazy_loading, setup_lazy_loading},
     listing::create_listing,
-    markdown::{Backlink, extract_frontmatter, markdown_to_html},
-    paths::{init_file_cache, process_paths},
-    static_files::process_static_files,
-    theme::generate_theme_css,
-    utils::is_not_hidden_dir,
+    markdown::{Backlink, link_snippets},
+    og::{generate_og_image, og_image_path},
+    pages::Pages,
+    paths::{
+        DirectoryPage, directory_page, init_file_cache, init_permalinks, link_target_key,
+        process_paths, route_for, route_key, set_flatten_static,
+    },
+    precompress::{Savings, precompress},
+    static_files::{copy_public_files, process_static_files},
+    theme::{generate_theme_css, resolve_palettes},
+    utils::{is_not_hidden_dir, prefix_root_urls},
+    redirects::{
+        Redirect, config_redirects, frontmatter_aliases, validate_redirects, write_alias_pages,
+        write_redirects_file,
+    },
+    robots::generate_robots,
     rss::generate_rss,
-    file_tree::{process_file_tree_assets, generate_file_tree_html},
+    site::{PageSummary, SiteContext, breadcrumbs},
+    tables::setup_tables,
+    file_tree::{build_file_tree, process_file_tree_assets, render_file_tree},
 };
+use chrono::Utc;
 use colored::Colorize;
 use minify_html::minify;
-use pulldown_cmark::{Event, Options, Parser, Tag};
-use std::collections::{HashMap, HashSet};
+use serde::Serialize;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::error::Error;
 use std::fs;
-use std::path::Path;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 use tera::Tera;
 use walkdir::WalkDir;
 
-pub fn build() -> Result<(), Box<dyn Error>> {
-    let dist = Path::new("dist");
-    println!("{}", "Starting build process...".cyan());
-    clear_directory_safely(dist)?;
-    create_directory_safely(dist)?;
-    let dist_static = dist.join("static");
+#[derive(Debug, Clone)]
+pub struct BuildOptions {
+    /// Site configuration to read, `Config.toml` by default.
+    pub config_path: PathBuf,
+    /// Keep `dist/` and skip pages and assets whose outputs are newer than
+    /// their sources. Any change to config, templates, asset URLs or
+    /// cross-page data (titles, dates, tags, backlinks, the set of content
+    /// files) still re-renders every page, and generated assets, listings and the feed
+    /// are always rewritten.
+    pub incremental: bool,
+    /// Fail the build on an image that can't be processed instead of
+    /// copying it unchanged, and on a page with invalid frontmatter instead
+    /// of skipping it.
+    pub strict: bool,
+    /// Use instead of `general.base_url` from the config file.
+    pub base_url: Option<String>,
+    /// Use instead of `general.base_path` from the config file.
+    pub base_path: Option<String>,
+    /// Also build pages marked `draft: true`, which are left out otherwise.
+    pub drafts: bool,
+}
+
+impl Default for BuildOptions {
+    fn default() -> Self {
+        BuildOptions {
+            config_path: PathBuf::from(DEFAULT_CONFIG),
+            incremental: false,
+            strict: false,
+            base_url: None,
+            base_path: None,
+            drafts: false,
+        }
+    }
+}
+
+/// Exposed to templates as `build`, e.g. for a "built with" footer.
+#[derive(Serialize)]
+struct BuildInfo {
+    version: &'static str,
+    time: String,
+}
+
+/// A page rendered by a build.
+#[derive(Debug, Clone)]
+pub struct PageInfo {
+    /// Markdown file under `content/`.
+    pub source: PathBuf,
+    /// Written file under `dist/`.
+    pub output: PathBuf,
+    pub url: String,
+    pub title: String,
+}
+
+/// What a build produced.
+#[derive(Debug, Default)]
+pub struct BuildReport {
+    /// Pages rendered, in `content/` order.
+    pub pages: Vec<PageInfo>,
+    /// Pages an incremental build found up to date.
+    pub pages_skipped: usize,
+    /// Images copied from `content/`; other files copied with them, like
+    /// PDFs, aren't counted.
+    pub images: usize,
+    /// Images copied unchanged because they couldn't be processed.
+    pub images_failed: usize,
+    pub listings: usize,
+    /// Series overview pages.
+    pub series: usize,
+    pub feeds: usize,
+    /// Per `precompress` format; empty when it is off.
+    pub compression: Vec<Savings>,
+    /// Everything logged as a warning during the build, e.g. images or
+    /// static files copied unchanged.
+    pub warnings: Vec<String>,
+    pub elapsed: Duration,
+}
+
+impl BuildReport {
+    /// Logs the counts as the summary shown after a build.
+    pub fn print(&self) {
+        info!("{}", "Build completed successfully!".green().bold());
+        let mut pages = plural(self.pages.len(), "page");
+        if self.pages_skipped > 0 {
+            pages.push_str(&format!(" ({} unchanged)", self.pages_skipped));
+        }
+        let mut images = plural(self.images, "image");
+        if self.images_failed > 0 {
+            images.push_str(&format!(" ({} failed)", self.images_failed));
+        }
+        let mut listings = plural(self.listings, "listing");
+        if self.series > 0 {
+            listings.push_str(&format!(", {} series", self.series));
+        }
+        info!(
+            "{} {}, {}, {}, {} in {:.2}s",
+            "Built".green().bold(),
+            pages.cyan(),
+            images.cyan(),
+            listings.cyan(),
+            plural(self.feeds, "feed").cyan(),
+            self.elapsed.as_secs_f64()
+        );
+        if !self.compression.is_empty() {
+            let formats: Vec<String> = self
+                .compression
+                .iter()
+                .map(|saved| {
+                    format!(
+                        "{} to .{} (saved {:.1} KB)",
+                        plural(saved.files, "file"),
+                        saved.format.extension(),
+                        saved.bytes as f64 / 1024.0
+                    )
+                })
+                .collect();
+            info!("{} {}", "Compressed".green().bold(), formats.join(", ").cyan());
+        }
+        if !self.warnings.is_empty() {
+            info!("{}", plural(self.warnings.len(), "warning").yellow());
+        }
+    }
+}
+
+fn plural(count: usize, noun: &str) -> String {
+    if count == 1 {
+        format!("{} {}", count, noun)
+    } else {
+        format!("{} {}s", count, noun)
+    }
+}
+
+/// Templates are loaded from `templates/` and its subfolders (so partials
+/// can live in e.g. `templates/partials/`); other files there are ignored.
+const TEMPLATE_GLOB: &str = "templates/**/*.{tera,html}";
+
+/// Directory of a content file relative to `content/` ("" for the root).
+fn section_of(path: &Path) -> String {
+    path.parent()
+        .and_then(|parent| parent.strip_prefix("content").ok())
+        .map(|parent| parent.to_string_lossy().replace('\\', "/"))
+        .unwrap_or_default()
+}
+
+/// Where the listing for a content directory (relative to `content/`, with
+/// `/` separators) is written, joined segment by segment.
+fn listing_output_dir(out_dir: &Path, relative_path: &str) -> PathBuf {
+    relative_path.split('/').fold(out_dir.to_path_buf(), |dir, segment| dir.join(segment))
+}
+
+/// Hash of everything that is rendered into more than one page.
+fn site_fingerprint(
+    config: &Config,
+    assets: &BTreeMap<String, String>,
+    site: &SiteContext,
+    backlink_map: &HashMap<String, BTreeMap<String, Backlink>>,
+) -> Result<u64, Box<dyn Error>> {
+    let mut hasher = DefaultHasher::new();
+    env!("CARGO_PKG_VERSION").hash(&mut hasher);
+    // The config as used, so `${VAR}`s and command-line overrides count.
+    // Going through `Value` sorts the keys of its maps.
+    serde_json::to_value(config)?.to_string().hash(&mut hasher);
+    // Hashed asset names change with their contents, and every page links
+    // to them.
+    assets.hash(&mut hasher);
+
+    let mut templates: Vec<PathBuf> = WalkDir::new("templates")
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .map(|e| e.path().to_path_buf())
+        .collect();
+    templates.sort();
+    for template in templates {
+        template.hash(&mut hasher);
+        fs::read(&template)?.hash(&mut hasher);
+    }
+
+    let mut content_files: Vec<PathBuf> = WalkDir::new("content")
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .map(|e| e.path().to_path_buf())
+        .collect();
+    content_files.sort();
+    content_files.hash(&mut hasher);
+
+    for page in &site.pages {
+        (&page.title, &page.url, &page.date, &page.updated, &page.tags, &page.description).hash(&mut hasher);
+        (&page.series, &page.series_order, &page.weight).hash(&mut hasher);
+    }
+
+    let mut targets: Vec<&String> = backlink_map.keys().collect();
+    targets.sort();
+    for target in targets {
+        target.hash(&mut hasher);
+        for backlink in backlink_map[target].values() {
+            backlink.hash(&mut hasher);
+        }
+    }
+
+    Ok(hasher.finish())
+}
+
+/// Loads the config named by `options`, applies its URL overrides and builds
+/// the site, logging a summary at the end.
+pub fn build(options: &BuildOptions) -> Result<(), Box<dyn Error>> {
+    let mut config = Config::load(&options.config_path)?;
+    config.override_urls(options.base_url.as_deref(), options.base_path.as_deref())?;
+    build_site(&config, options)?.print();
+    Ok(())
+}
+
+/// Builds the site in the current directory (`content/`, `templates/`,
+/// `static/`) into `paths.output_dir` with an already loaded `config`; the config path
+/// and URL overrides in `options` are not used.
+pub fn build_site(config: &Config, options: &BuildOptions) -> Result<BuildReport, Box<dyn Error>> {
+    let started = Instant::now();
+    let mut summary = BuildReport::default();
+    // Anything logged before this build (e.g. by `serve`) isn't part of it.
+    logger::take_warnings();
+    let dist = Path::new(&config.paths.output_dir);
+    info!("{}", "Starting build process...".cyan());
+    let previous_state = if options.incremental {
+        BuildState::load()
+    } else {
+        None
+    };
+    // Incremental builds update dist in place; full builds render into a
+    // staging directory that replaces dist only once everything succeeded,
+    // so a failed build keeps the last good site.
+    let out_dir = if previous_state.is_some() {
+        dist
+    } else {
+        let staging = Path::new(STAGING_DIR);
+        clear_directory_safely(staging, true)?;
+        staging
+    };
+    create_directory_safely(out_dir)?;
+    let dist_static = out_dir.join("static");
     create_directory_safely(&dist_static)?;
 
     let lazy_dir = dist_static.join("lazy");
     create_directory_safely(&lazy_dir)?;
 
-    let config_str = fs::read_to_string("Config.toml")
-        .map_err(|e| format!("Failed to read Config.toml: {}", e))?;
-    let config: Config =
-        toml::from_str(&config_str).map_err(|e| format!("Failed to parse Config.toml: {}", e))?;
-    config
-        .validate()
-        .map_err(|e| format!("Invalid configuration: {}", e))?;
-
-    let theme_css_path = dist_static.join("theme.css");
-    generate_theme_css(&config, &theme_css_path)?;
-
-    setup_lazy_loading(&dist_static)?;
-    process_file_tree_assets(&dist_static)?;
-    process_static_files(&dist_static)?;
-
-    println!("{}", "Loading Templates defined in templates".blue());
-    let tera = Tera::new("templates/**/*").map_err(|e| {
-        eprintln!("{}", format!("Error loading templates: {}", e).red());
-        Box::new(e) as Box<dyn Error>
+    // Asset URLs keyed by unhashed file name, e.g. `assets["theme.css"]`.
+    let mut assets: BTreeMap<String, String> = BTreeMap::new();
+    assets.insert("theme.css".to_string(), generate_theme_css(config, &dist_static)?);
+    // Palettes a page may force with `theme:` in its frontmatter.
+    let theme_names: Vec<String> = resolve_palettes(config)?.into_iter().map(|(name, _)| name).collect();
+    if config.images.lazy_mode == LazyMode::Placeholder {
+        assets.extend(setup_lazy_loading(&dist_static)?);
+    }
+    assets.extend(setup_code_blocks(&dist_static)?);
+    assets.extend(setup_callouts(&dist_static)?);
+    assets.extend(setup_tables(&dist_static)?);
+    if config.content.heading_anchors {
+        assets.extend(setup_heading_anchors(&dist_static)?);
+    }
+    if config.content.external_link_icon {
+        assets.extend(setup_external_links(&dist_static)?);
+    }
+    if config.file_tree.enable {
+        assets.extend(process_file_tree_assets(&dist_static)?);
+    }
+    assets.extend(process_static_files(&dist_static, &config.build)?);
+
+    debug!("{}", "Loading Templates defined in templates".blue());
+    let mut tera = Tera::new(TEMPLATE_GLOB).map_err(|e| {
+        // Tera names the failing file at the top level and keeps the parse
+        // error itself in the source chain.
+        let mut message = e.to_string();
+        let mut source = e.source();
+        while let Some(cause) = source {
+            message.push_str(&format!(": {}", cause));
+            source = cause.source();
+        }
+        error!("{}", format!("Error loading templates: {}", message).red());
+        message
     })?;
+    register_filters(&mut tera);
+    if logger::enabled(logger::Level::Debug) {
+        let mut names: Vec<&str> = tera.get_template_names().collect();
+        names.sort();
+        debug!("{} {}", "Loaded templates:".green(), names.join(", "));
+    }
 
     let minify_cfg = minify_html::Cfg {
-        minify_js: false,
+        minify_js: config.build.minify_inline_js,
         minify_css: true,
         ..Default::default()
     };
 
+    set_flatten_static(config.paths.flatten_static);
     init_file_cache();
-    generate_rss(dist, &config)?;
+    // Every page is read and parsed once, here; the passes below share it.
+    let pages = Pages::load(options.drafts, options.strict)?;
+    init_permalinks(pages.iter().map(|page| (page.path.as_path(), &page.frontmatter)))?;
 
-    let mut backlink_map: HashMap<String, HashSet<(String, String)>> = HashMap::new();
-    println!("{}", "Collecting backlinks...".blue());
+    // Target route key -> linking page route -> backlink.
+    let mut backlink_map: HashMap<String, BTreeMap<String, Backlink>> = HashMap::new();
+    let mut site = SiteContext::default();
+    let mut page_titles: HashMap<String, String> = HashMap::new();
+    let mut page_weights: HashMap<String, i64> = HashMap::new();
+    let mut dir_titles: HashMap<String, String> = HashMap::new();
+    let mut redirects: Vec<Redirect> = config_redirects(config);
+    // Keys of every route a page or listing is published at.
+    let mut routes: HashSet<String> = HashSet::new();
+    let mut translations = Translations::default();
+    info!("{}", "Collecting backlinks...".blue());
     for entry in WalkDir::new("content")
+        .sort_by_file_name()
         .into_iter()
         .filter_entry(is_not_hidden_dir)
         .filter_map(|e| e.ok())
     {
-        if entry.path().is_file() && entry.path().extension().and_then(|s| s.to_str()) == Some("md")
-        {
-            let content = fs::read_to_string(entry.path())?;
-            let (frontmatter, md_content) = extract_frontmatter(&content)?;
-            let source_path = entry
-                .path()
-                .strip_prefix("content")?
-                .to_string_lossy()
-                .replace('\\', "/");
-            let source_title = frontmatter["title"]
-                .as_str()
-                .unwrap_or("Untitled")
-                .to_string();
-
-            let processed_content = process_paths(md_content, entry.path());
-            let mut options = Options::empty();
-            options.insert(Options::ENABLE_GFM);
-            let parser = Parser::new_ext(&processed_content, options);
-
-            for event in parser {
-                if let Event::Start(Tag::Link { ref dest_url, .. }) = event {
-                    if !dest_url.starts_with("http") && !dest_url.starts_with("wiki:") {
-                        let target_path = dest_url
-                            .trim_start_matches('/')
-                            .replace('\\', "/")
-                            .replace(".md", "");
-
-                        let clean_source_path = if source_path == "index.md" {
-                            "/".to_string()
-                        } else {
-                            format!("/{}", source_path.replace(".md", ""))
-                        };
-
-                        backlink_map
-                            .entry(target_path)
-                            .or_insert_with(HashSet::new)
-                            .insert((source_title.clone(), clean_source_path));
-                    }
+        if let Some(page) = pages.get(entry.path()) {
+            let frontmatter = &page.frontmatter;
+            let source_route = route_for(entry.path());
+            let source_title = frontmatter.title.clone();
+            page_titles.insert(source_route.trim_start_matches('/').to_string(), source_title.clone());
+            if let Some(weight) = frontmatter.weight {
+                page_weights.insert(source_route.trim_start_matches('/').to_string(), weight);
+            }
+
+            let section = section_of(entry.path());
+            if entry.file_name() == "index.md" {
+                dir_titles.insert(section.clone(), source_title.clone());
+            }
+            site.pages.push(PageSummary::from_frontmatter(
+                frontmatter,
+                source_route.clone(),
+                section,
+            ));
+            routes.insert(route_key(&source_route));
+            translations.add(
+                entry.path(),
+                config.site_url(&source_route),
+                &config.i18n.languages,
+            );
+            redirects.extend(frontmatter_aliases(frontmatter, &source_route, entry.path()));
+
+            let processed_content = process_paths(&page.markdown, entry.path());
+            for (dest_url, context) in link_snippets(&processed_content) {
+                if let Some(target) = link_target_key(&dest_url) {
+                    // The first link from a page provides its snippet.
+                    backlink_map
+                        .entry(target)
+                        .or_default()
+                        .entry(source_route.clone())
+                        .or_insert_with(|| Backlink {
+                            title: source_title.clone(),
+                            path: source_route.clone(),
+                            context,
+                        });
                 }
             }
+        } else if entry.file_type().is_dir() {
+            routes.insert(route_key(&section_of(&entry.path().join("index.md"))));
         }
     }
+    validate_redirects(&redirects, &routes)?;
+    generate_rss(out_dir, config, &pages)?;
+    summary.feeds += 1;
+
+    site.sort_pages();
+    site.collect_series();
+    routes.extend(redirects.iter().map(|redirect| route_key(&redirect.from)));
+    site.validate_series(&routes)?;
+
+    // The tree is walked once, reusing the titles read above; each page only
+    // re-renders its highlighting.
+    let file_tree = if config.file_tree.enable {
+        build_file_tree(Path::new("content"), Path::new(""), config, &page_titles, &page_weights)
+    } else {
+        Vec::new()
+    };
+
+    // Shared by every content page and listing.
+    let mut base_context = tera::Context::new();
+    base_context.insert("site", &site);
+    base_context.insert("assets", &assets);
+    base_context.insert("general", &config.general);
+    base_context.insert("config", config);
+    base_context.insert(
+        "build",
+        &BuildInfo {
+            version: env!("CARGO_PKG_VERSION"),
+            time: Utc::now().to_rfc3339(),
+        },
+    );
+
+    let fingerprint = site_fingerprint(config, &assets, &site, &backlink_map)?;
+    let render_all = previous_state
+        .as_ref()
+        .is_none_or(|state| state.fingerprint != fingerprint);
+    if previous_state.is_some() && render_all {
+        info!("{}", "Site-wide inputs changed, re-rendering every page".yellow());
+    }
+    let mut outputs: HashSet<PathBuf> = assets
+        .values()
+        .map(|url| out_dir.join(url.trim_start_matches('/')))
+        .collect();
 
     for entry in WalkDir::new("content")
+        .sort_by_file_name()
         .into_iter()
         .filter_entry(is_not_hidden_dir)
         .filter_map(|e| e.ok())
@@ -125,119 +463,228 @@ pub fn build() -> Result<(), Box<dyn Error>> {
             }
 
             if entry.path().extension().and_then(|s| s.to_str()) == Some("md") {
-                let relative_path = entry
-                    .path()
-                    .strip_prefix("content")?
-                    .to_string_lossy()
-                    .replace('\\', "/");
-                let rel_path = Path::new(&relative_path);
-                let output_path = if relative_path == "index.md" {
-                    dist.join("index.html")
+                let Some(page) = pages.get(entry.path()) else {
+                    continue;
+                };
+                let current_route = route_for(entry.path());
+                let output_path = if current_route == "/" {
+                    out_dir.join("index.html")
                 } else {
-                    let output_dir = dist.join(rel_path.with_extension(""));
+                    let output_dir = out_dir.join(current_route.trim_start_matches('/'));
                     create_directory_safely(&output_dir)?;
                     output_dir.join("index.html")
                 };
+                outputs.insert(output_path.clone());
+                if !render_all && is_up_to_date(entry.path(), &output_path) {
+                    // An unchanged page keeps the Open Graph image it was
+                    // last rendered with.
+                    let og_path = og_image_path(&current_route, &dist_static);
+                    if config.og.generate && og_path.exists() {
+                        outputs.insert(og_path);
+                    }
+                    summary.pages_skipped += 1;
+                    continue;
+                }
 
-                let content = fs::read_to_string(entry.path())?;
-                let (frontmatter, md_content) = extract_frontmatter(&content)?;
-                let (mut html_content, toc) = markdown_to_html(md_content, entry.path());
-                html_content = add_lazy_loading(&html_content, config.images.compress_to_webp);
+                let frontmatter = &page.frontmatter;
+                let (html_content, toc) = page.rendered(config);
+                let mut html_content = add_lazy_loading(html_content, &config.images.lazy_mode);
                 if config.images.compress_to_webp {
-                    html_content = html_content
-                        .replace(".jpg", ".webp")
-                        .replace(".jpeg", ".webp")
-                        .replace(".png", ".webp");
+                    html_content = use_webp_urls(&html_content);
                 }
 
-                let mut context = tera::Context::new();
-                let title = frontmatter["title"]
-                    .as_str()
-                    .unwrap_or("Untitled")
-                    .to_string();
-                let current_route = if relative_path == "index.md" {
-                    "/".to_string()
+                let mut context = base_context.clone();
+                let title = frontmatter.title.clone();
+
+                let file_tree_html = render_file_tree(&file_tree, &current_route);
+                let page_crumb = (entry.file_name() != "index.md").then_some((title.as_str(), current_route.as_str()));
+                let breadcrumbs = breadcrumbs(&section_of(entry.path()), page_crumb, &dir_titles);
+
+                let description = frontmatter.description.as_deref().unwrap_or_default();
+                let image = frontmatter
+                    .image
+                    .as_deref()
+                    .map(|image| config.site_url(image))
+                    .unwrap_or_default();
+                let author = config
+                    .page_author(frontmatter)
+                    .map_err(|e| format!("Invalid author in {}: {}", entry.path().display(), e))?;
+                let canonical_url = config.site_url(&current_route);
+                let og_image = if !image.is_empty() {
+                    image.clone()
+                } else if config.og.generate {
+                    let og_path = generate_og_image(&title, &config.general.title, &current_route, &dist_static)?;
+                    let og_url = static_url(&dist_static, &og_path);
+                    outputs.insert(og_path);
+                    config.site_url(&og_url)
                 } else {
-                    format!("/{}", relative_path.replace(".md", ""))
+                    String::new()
                 };
 
-                // Generate file tree HTML specific to this route
-                let file_tree_html = generate_file_tree_html(&config, &current_route)?;
-
                 context.insert("title", &title);
+                context.insert("date", &frontmatter.date);
+                context.insert("updated", frontmatter.updated());
+                context.insert("breadcrumbs", &breadcrumbs);
+                context.insert("description", description);
+                context.insert("image", &image);
+                context.insert("author", &author);
+                if let Some(theme) = frontmatter.theme.as_deref() {
+                    if !theme_names.iter().any(|name| name == theme) {
+                        return Err(format!(
+                            "Unknown theme '{}' in {} (available: {})",
+                            theme,
+                            entry.path().display(),
+                            theme_names.join(", ")
+                        )
+                        .into());
+                    }
+                    context.insert("page_theme", theme);
+                }
+                if let Some((lang, _)) = page_language(entry.path(), &config.i18n.languages) {
+                    context.insert("lang", lang);
+                }
+                context.insert("translations", &translations.of(entry.path(), &config.i18n.languages));
+                context.insert("canonical_url", &canonical_url);
+                context.insert("og_image", &og_image);
+                context.insert(
+                    "jsonld",
+                    &article_json_ld(&ArticleData {
+                        title: &title,
+                        url: &canonical_url,
+                        date: &frontmatter.date,
+                        updated: frontmatter.updated(),
+                        author: author.as_ref(),
+                        description,
+                        image: &og_image,
+                    }),
+                );
                 context.insert("markdown", &html_content);
-                context.insert("frontmatter", &frontmatter);
-                context.insert("table_of_contents", &toc);
+                context.insert("frontmatter", &frontmatter.raw);
+                context.insert("table_of_contents", toc);
                 context.insert("has_images", &html_content.contains("<img"));
+                context.insert("math", &config.content.math);
                 context.insert("file_tree", &file_tree_html);
                 context.insert("current_route", &current_route);
-                context.insert("giscus_enabled", &config.giscus.is_enabled_for_route(&current_route));
-                context.insert("giscus", &config.giscus);
+                let giscus = config.giscus.for_route(&current_route);
+                context.insert("giscus_enabled", &giscus.is_some());
+                context.insert("giscus", &giscus);
                 context.insert("site_name", &config.general.base_url);
 
-                let current_path = relative_path.replace(".md", "");
-                let clean_current_path = if current_path == "index" {
-                    "".to_string()
-                } else {
-                    current_path
-                };
-                let backlinks: Vec<Backlink> = backlink_map
-                    .get(&clean_current_path)
-                    .unwrap_or(&HashSet::new())
-                    .iter()
-                    .map(|(title, path)| Backlink {
-                        title: title.clone(),
-                        path: path.clone(),
-                    })
-                    .collect();
+                let mut backlinks: Vec<Backlink> = backlink_map
+                    .get(&route_key(&current_route))
+                    .map(|sources| sources.values().cloned().collect())
+                    .unwrap_or_default();
+                backlinks.sort_by(|a, b| a.title.cmp(&b.title));
+                context.insert("backlinks_count", &backlinks.len());
                 context.insert("backlinks", &backlinks);
+                context.insert("series", &site.series_position(&current_route));
+                let (prev_post, next_post) = site.neighbors(&current_route, &config.content.nav_scope);
+                context.insert("prev_post", &prev_post);
+                context.insert("next_post", &next_post);
 
-                let rendered = tera.render("content.tera", &context)?;
+                let template = frontmatter.template.as_deref().unwrap_or("content.tera");
+                if !tera.get_template_names().any(|name| name == template) {
+                    return Err(format!(
+                        "Template '{}' requested by {} was not found in templates/",
+                        template,
+                        entry.path().display().to_string().replace('\\', "/")
+                    )
+                    .into());
+                }
+                let rendered = prefix_root_urls(&tera.render(template, &context)?, &config.general.base_path);
                 let minified = minify(rendered.as_bytes(), &minify_cfg);
                 safely_write_file(&output_path, String::from_utf8(minified)?.as_str())?;
+                summary.pages.push(PageInfo {
+                    source: entry.path().to_path_buf(),
+                    output: output_path.clone(),
+                    url: current_route.clone(),
+                    title: title.clone(),
+                });
 
-                println!(
+                debug!(
                     "{} {} -> {} (with lazy loading)",
                     "Converting".green(),
                     entry.path().display().to_string().replace('\\', "/").yellow(),
                     output_path.display().to_string().replace('\\', "/").yellow(),
                 );
             } else {
-                process_content_images(&entry, &dist_static, &lazy_dir, &config)?;
+                match process_content_images(
+                    &entry,
+                    &dist_static,
+                    &lazy_dir,
+                    config,
+                    previous_state.is_some(),
+                ) {
+                    Ok(written) => {
+                        outputs.extend(written);
+                        if is_image(entry.path()) {
+                            summary.images += 1;
+                        }
+                    }
+                    Err(e) if options.strict => {
+                        return Err(format!("Failed to process {}: {}", entry.path().display(), e).into());
+                    }
+                    Err(e) => {
+                        warn!(
+                            "{} {}: {} (copied unchanged)",
+                            "Could not process".yellow(),
+                            entry.path().display().to_string().replace('\\', "/"),
+                            e
+                        );
+                        outputs.insert(copy_content_file(&entry, &dist_static, config)?);
+                        if is_image(entry.path()) {
+                            summary.images_failed += 1;
+                        }
+                    }
+                }
             }
         } else if entry.path().is_dir() && entry.path().display().to_string() != "content" {
             let file_name = entry.file_name().to_string_lossy();
             if file_name.starts_with(".") {
                 continue;
             }
+            // An index.md at the directory's route is rendered as its page.
+            if let DirectoryPage::Index(index) = directory_page(entry.path()) {
+                debug!(
+                    "{} {} {}",
+                    "Skipping listing for".green(),
+                    entry.path().display().to_string().replace('\\', "/").yellow(),
+                    format!("(uses {})", index.display().to_string().replace('\\', "/")).dimmed()
+                );
+                continue;
+            }
 
             let relative_path = entry
                 .path()
                 .strip_prefix("content")?
                 .to_string_lossy()
                 .replace('\\', "/");
-            let output_dir = dist.join(relative_path.replace('/', "\\"));
+            let output_dir = listing_output_dir(out_dir, &relative_path);
             create_directory_safely(&output_dir)?;
-            let items = create_listing(entry.path())?;
+            let items = create_listing(entry.path(), config, &pages)?;
 
-            let mut context = tera::Context::new();
+            let mut context = base_context.clone();
             let current_route = format!("/{}", relative_path);
-            let file_tree_html = generate_file_tree_html(&config, &current_route)?;
+            let file_tree_html = render_file_tree(&file_tree, &current_route);
 
             context.insert("items", &items);
             context.insert("dir_path", &relative_path);
             context.insert("compress_to_webp", &config.images.compress_to_webp);
             context.insert("file_tree", &file_tree_html);
             context.insert("current_route", &current_route);
+            context.insert("breadcrumbs", &breadcrumbs(&relative_path, None, &dir_titles));
+            if let Some((lang, _)) = page_language(entry.path(), &config.i18n.languages) {
+                context.insert("lang", lang);
+            }
 
-            let rendered = tera.render("listing.tera", &context)?;
+            let rendered = prefix_root_urls(&tera.render("listing.tera", &context)?, &config.general.base_path);
             let minified = minify(rendered.as_bytes(), &minify_cfg);
-            safely_write_file(
-                &output_dir.join("index.html"),
-                String::from_utf8(minified)?.as_str(),
-            )?;
+            let listing_path = output_dir.join("index.html");
+            safely_write_file(&listing_path, String::from_utf8(minified)?.as_str())?;
+            outputs.insert(listing_path);
+            summary.listings += 1;
 
-            println!(
+            debug!(
                 "{} {} -> {}",
                 "Creating listing for".green(),
                 entry.path().display().to_string().replace('\\', "/").yellow(),
@@ -246,6 +693,265 @@ pub fn build() -> Result<(), Box<dyn Error>> {
         }
     }
 
-    println!("{}", "Build completed successfully!".green().bold());
-    Ok(())
-}
\ No newline at end of file
+    if !site.series.is_empty() {
+        if tera.get_template_names().any(|name| name == "series.tera") {
+            for series in &site.series {
+                let output_dir = out_dir.join(series.url.trim_start_matches('/'));
+                create_directory_safely(&output_dir)?;
+                let mut context = base_context.clone();
+                context.insert("series", series);
+                context.insert("file_tree", &render_file_tree(&file_tree, &series.url));
+                context.insert("current_route", &series.url);
+                context.insert("breadcrumbs", &breadcrumbs("", Some((&series.name, &series.url)), &dir_titles));
+                // Series overviews live outside the language directories.
+                if let Some(lang) = config.i18n.languages.first() {
+                    context.insert("lang", lang);
+                }
+
+                let rendered = prefix_root_urls(&tera.render("series.tera", &context)?, &config.general.base_path);
+                let minified = minify(rendered.as_bytes(), &minify_cfg);
+                let series_path = output_dir.join("index.html");
+                safely_write_file(&series_path, String::from_utf8(minified)?.as_str())?;
+                outputs.insert(series_path);
+                summary.series += 1;
+            }
+        } else {
+            warn!(
+                "{} {}",
+                "No templates/series.tera,".yellow(),
+                "skipping series overview pages"
+            );
+        }
+    }
+
+    generate_robots(out_dir, config)?;
+    outputs.extend(write_alias_pages(out_dir, &redirects, config)?);
+    if config.build.emit_redirects {
+        outputs.insert(write_redirects_file(out_dir, &redirects)?);
+    }
+    // Last, so anything generated above takes precedence over a public file.
+    let previous_outputs = previous_state.as_ref().map_or(&[][..], |state| &state.outputs[..]);
+    let public_files = copy_public_files(
+        Path::new(&config.paths.public_dir),
+        out_dir,
+        &outputs,
+        previous_outputs,
+    )?;
+    outputs.extend(public_files);
+    // After everything else, so every written file gets its compressed copies.
+    let (compressed, savings) = precompress(out_dir, &config.build.precompress)?;
+    outputs.extend(compressed);
+    summary.compression = savings;
+
+    if let Some(previous_state) = &previous_state {
+        let removed = prune_orphans(previous_state, &outputs, out_dir)?;
+        if removed > 0 {
+            info!("{} {} orphaned outputs", "Removed".green(), removed);
+        }
+    }
+    if out_dir != dist {
+        swap_directory(out_dir, dist)?;
+    }
+    let in_dist = |path: PathBuf| match path.strip_prefix(out_dir) {
+        Ok(relative) => dist.join(relative),
+        Err(_) => path,
+    };
+    let mut outputs: Vec<PathBuf> = outputs.into_iter().map(in_dist).collect();
+    outputs.sort();
+    for page in &mut summary.pages {
+        page.output = in_dist(std::mem::take(&mut page.output));
+    }
+    BuildState {
+        fingerprint,
+        outputs,
+    }
+    .save()?;
+
+    summary.warnings = logger::take_warnings();
+    summary.elapsed = started.elapsed();
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nested_listing_dirs_are_joined_by_segment() {
+        let output_dir = listing_output_dir(Path::new("dist"), "blog/2024/drafts");
+        assert_eq!(output_dir, Path::new("dist").join("blog").join("2024").join("drafts"));
+        let segments: Vec<String> = output_dir
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy().to_string())
+            .collect();
+        assert_eq!(segments, ["dist", "blog", "2024", "drafts"]);
+    }
+
+    /// Every file under `dir`, keyed by its path relative to `dir`.
+    fn snapshot(dir: &Path) -> BTreeMap<PathBuf, Vec<u8>> {
+        WalkDir::new(dir)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|entry| entry.file_type().is_file())
+            .map(|entry| {
+                let relative = entry.path().strip_prefix(dir).unwrap().to_path_buf();
+                (relative, fs::read(entry.path()).unwrap())
+            })
+            .collect()
+    }
+
+    /// A temporary site, made the working directory until dropped, even when
+    /// a test fails halfway. Holds the global state lock, since builds read
+    /// `content/` relative to the working directory and fill shared caches.
+    struct TestSite {
+        dir: PathBuf,
+        previous_dir: PathBuf,
+        _lock: std::sync::MutexGuard<'static, ()>,
+    }
+
+    impl TestSite {
+        /// Writes a site using the repository's templates and static files,
+        /// with one page per `(path, frontmatter, body)`.
+        fn new(name: &str, pages: &[(&str, &str, &str)]) -> TestSite {
+            let lock = crate::utils::lock_global_state();
+            let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+            let dir = std::env::temp_dir().join(format!("sekiei-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            for assets in ["templates", "static"] {
+                fs::create_dir_all(dir.join(assets)).unwrap();
+                for entry in fs::read_dir(manifest_dir.join(assets)).unwrap() {
+                    let entry = entry.unwrap();
+                    fs::copy(entry.path(), dir.join(assets).join(entry.file_name())).unwrap();
+                }
+            }
+            for (path, frontmatter, body) in pages {
+                let path = dir.join("content").join(path);
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(path, format!("---\n{}\n---\n{}\n", frontmatter, body)).unwrap();
+            }
+            let previous_dir = std::env::current_dir().unwrap();
+            std::env::set_current_dir(&dir).unwrap();
+            TestSite { dir, previous_dir, _lock: lock }
+        }
+    }
+
+    impl Drop for TestSite {
+        fn drop(&mut self) {
+            let _ = std::env::set_current_dir(&self.previous_dir);
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    fn example_config() -> Config {
+        toml::from_str(include_str!("../Config.example.toml")).unwrap()
+    }
+
+    #[test]
+    fn building_twice_gives_identical_output() {
+        let pages = [
+            ("index.md", "title: Home\ndate: 2024-01-01", "Start at [[Beta]] or [[Alpha]]."),
+            ("notes/alpha.md", "title: Alpha\ndate: 2024-02-01\ntags: [z, a, m]\nseries: Intro", "See [[Beta]]."),
+            ("notes/beta.md", "title: Beta\ndate: 2024-03-01\ntags: [m, b]\nseries: Intro", "Back to [[Alpha]]."),
+            ("notes/gamma.md", "title: Gamma\ndate: 2024-03-01\ntags: [b, a]", "Both [[Alpha]] and [[Beta]]."),
+        ];
+        let config = example_config();
+        let _site = TestSite::new("determinism", &pages);
+        build_site(&config, &BuildOptions::default()).unwrap();
+        let first = snapshot(Path::new("dist"));
+        build_site(&config, &BuildOptions::default()).unwrap();
+        let second = snapshot(Path::new("dist"));
+
+        assert!(first.contains_key(Path::new("notes/beta/index.html")));
+        assert_eq!(first.keys().collect::<Vec<_>>(), second.keys().collect::<Vec<_>>());
+        for (path, contents) in &first {
+            assert!(contents == &second[path], "{} differs between builds", path.display());
+        }
+    }
+
+    #[test]
+    fn build_report_lists_pages_and_warnings() {
+        let pages = [
+            ("index.md", "title: Home\ndate: 2024-01-01", "Hello."),
+            ("notes/alpha.md", "title: Alpha\ndate: 2024-02-01", "Alpha."),
+            ("notes/broken.md", "date: 2024-02-01", "No title."),
+        ];
+        let config = example_config();
+        let _site = TestSite::new("report", &pages);
+        fs::write(
+            "content/notes/diagram.svg",
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="1" height="1"></svg>"#,
+        )
+        .unwrap();
+        fs::write("content/notes/paper.pdf", "%PDF-1.4").unwrap();
+        let report = build_site(&config, &BuildOptions::default()).unwrap();
+
+        let rendered: Vec<(&str, &str)> = report
+            .pages
+            .iter()
+            .map(|page| (page.url.as_str(), page.title.as_str()))
+            .collect();
+        assert_eq!(rendered, [("/", "Home"), ("/notes/alpha", "Alpha")]);
+        assert_eq!(report.pages[1].source, Path::new("content/notes/alpha.md"));
+        assert_eq!(report.pages[1].output, Path::new("dist/notes/alpha/index.html"));
+        assert_eq!(report.pages_skipped, 0);
+        assert_eq!(report.images, 1, "the PDF is copied but not counted");
+        assert_eq!(report.images_failed, 0);
+        assert_eq!(report.listings, 1);
+        assert_eq!(report.feeds, 1);
+        assert!(!report.warnings.is_empty());
+        assert!(
+            report.warnings.iter().all(|warning| warning.contains("notes/broken.md")),
+            "{:?}",
+            report.warnings
+        );
+    }
+
+    #[test]
+    fn drafts_are_left_out_unless_asked_for() {
+        let pages = [
+            ("notes/alpha.md", "title: Alpha\ndate: 2024-02-01", "Alpha."),
+            ("notes/secret.md", "title: Secret plans\ndate: 2024-03-01\ndraft: true", "Secret."),
+        ];
+        let config = example_config();
+        let _site = TestSite::new("drafts", &pages);
+
+        let report = build_site(&config, &BuildOptions::default()).unwrap();
+        assert!(report.pages.iter().all(|page| page.title != "Secret plans"));
+        assert!(!Path::new("dist/notes/secret").exists());
+        let listing = fs::read_to_string("dist/notes/index.html").unwrap();
+        assert!(listing.contains("Alpha"));
+        for output in ["dist/notes/index.html", "dist/notes/alpha/index.html", "dist/rss.xml"] {
+            let html = fs::read_to_string(output).unwrap();
+            assert!(!html.contains("Secret plans"), "{} mentions the draft", output);
+            assert!(!html.contains("notes/secret"), "{} links the draft", output);
+        }
+
+        let options = BuildOptions {
+            drafts: true,
+            ..BuildOptions::default()
+        };
+        let report = build_site(&config, &options).unwrap();
+        assert!(report.pages.iter().any(|page| page.title == "Secret plans"));
+        assert!(fs::read_to_string("dist/rss.xml").unwrap().contains("Secret plans"));
+        assert!(fs::read_to_string("dist/notes/index.html").unwrap().contains("Secret plans"));
+    }
+
+    #[test]
+    fn linked_pages_list_their_backlinks() {
+        let pages = [
+            ("notes/alpha.md", "title: Alpha\ndate: 2024-02-01", "See [[beta]] for more."),
+            ("notes/beta.md", "title: Beta\ndate: 2024-02-02", "Unlinked."),
+        ];
+        let config = example_config();
+        let _site = TestSite::new("backlinks", &pages);
+        build_site(&config, &BuildOptions::default()).unwrap();
+
+        let beta = fs::read_to_string("dist/notes/beta/index.html").unwrap();
+        let backlinks = &beta[beta.find("class=backlinks").expect("no backlinks section")..];
+        assert!(backlinks.contains("Backlinks (1)"), "{}", backlinks);
+        assert!(backlinks.contains("href=/notes/alpha>Alpha</a>"), "{}", backlinks);
+        assert!(backlinks.contains("See"), "the snippet is shown: {}", backlinks);
+        let alpha = fs::read_to_string("dist/notes/alpha/index.html").unwrap();
+        assert!(!alpha.contains("class=backlinks"), "nothing links to alpha");
+    }
+}
@@ -0,0 +1,222 @@
+use crate::logger::debug;
+use crate::file_ops::{static_url, write_hashed_file};
+use colored::Colorize;
+use css_minify::optimizations::{Level as CssLevel, Minifier as CssMinifier};
+use lazy_static::lazy_static;
+use pulldown_cmark::{BlockQuoteKind, Event, Tag, TagEnd};
+use regex::Regex;
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::Path;
+
+const KNOWN_TYPES: [&str; 7] = ["note", "tip", "important", "warning", "caution", "danger", "info"];
+
+lazy_static! {
+    static ref CALLOUT_MARKER: Regex = Regex::new(r"^\[!([A-Za-z]+)\][+-]?\s*(.*)$").unwrap();
+}
+
+/// Writes the hashed callout stylesheet and returns its URL keyed by
+/// unhashed file name.
+pub fn setup_callouts(dist_static: &Path) -> Result<HashMap<String, String>, Box<dyn Error>> {
+    // css_minify can't parse a `var()` nested in another's fallback, so the
+    // defaults are set as properties on `.callout` and overridden per type.
+    let callouts_css = r#"
+.callout {
+    --callout-color: var(--border-color);
+    --callout-title-color: var(--text-color);
+    margin: 1rem 0;
+    padding: 0.75rem 1rem;
+    border-left: 4px solid var(--callout-color);
+    background: var(--secondary-background);
+}
+
+.callout-title {
+    font-weight: bold;
+    color: var(--callout-title-color);
+    margin-bottom: 0.25rem;
+}
+
+.callout-note, .callout-info { --callout-color: #4493f8; --callout-title-color: #4493f8; }
+.callout-tip { --callout-color: #3fb950; --callout-title-color: #3fb950; }
+.callout-important { --callout-color: #ab7df8; --callout-title-color: #ab7df8; }
+.callout-warning { --callout-color: #d29922; --callout-title-color: #d29922; }
+.callout-caution, .callout-danger { --callout-color: #f85149; --callout-title-color: #f85149; }
+"#;
+
+    let minified_css = CssMinifier::default().minify(callouts_css, CssLevel::Three)?;
+    let css_path = write_hashed_file(dist_static, "callouts.css", minified_css.as_bytes())?;
+
+    debug!("{}", "Generated and minified callouts.css".green());
+    Ok(HashMap::from([(
+        "callouts.css".to_string(),
+        static_url(dist_static, &css_path),
+    )]))
+}
+
+/// Turns blockquotes starting with a `[!TYPE]` marker into
+/// `<div class="callout callout-type"><div class="callout-title">Type</div>...</div>`.
+/// GFM alert kinds arrive already parsed; other markers (`[!DANGER]`,
+/// `[!INFO]`, anything else) are read from the first paragraph, and text
+/// after the marker becomes a custom title. Unknown types get a plain
+/// `callout` class.
+pub fn render_callouts(events: Vec<Event>) -> Vec<Event> {
+    let mut output = Vec::with_capacity(events.len());
+    // One entry per open blockquote: whether it was turned into a callout.
+    let mut open_quotes: Vec<bool> = Vec::new();
+    let mut i = 0;
+
+    while i < events.len() {
+        match &events[i] {
+            Event::Start(Tag::BlockQuote(Some(kind))) => {
+                output.push(callout_start(gfm_kind_name(*kind), ""));
+                open_quotes.push(true);
+                i += 1;
+            }
+            Event::Start(Tag::BlockQuote(None)) => match parse_marker(&events, i + 1) {
+                Some((kind, title, resume, reopen_paragraph)) => {
+                    output.push(callout_start(&kind, &title));
+                    if reopen_paragraph {
+                        output.push(Event::Start(Tag::Paragraph));
+                    }
+                    open_quotes.push(true);
+                    i = resume;
+                }
+                None => {
+                    output.push(events[i].clone());
+                    open_quotes.push(false);
+                    i += 1;
+                }
+            },
+            Event::End(TagEnd::BlockQuote(_)) => {
+                if open_quotes.pop().unwrap_or(false) {
+                    output.push(Event::Html("</div>".into()));
+                } else {
+                    output.push(events[i].clone());
+                }
+                i += 1;
+            }
+            _ => {
+                output.push(events[i].clone());
+                i += 1;
+            }
+        }
+    }
+    output
+}
+
+/// Looks for `[!TYPE] optional title` at the start of the paragraph opening
+/// at `start`. Returns the type, the title, the index to continue from and
+/// whether the paragraph goes on after the marker line (and so has to be
+/// reopened as the body).
+fn parse_marker(events: &[Event], start: usize) -> Option<(String, String, usize, bool)> {
+    if !matches!(events.get(start), Some(Event::Start(Tag::Paragraph))) {
+        return None;
+    }
+    let mut first_line = String::new();
+    let mut end = start + 1;
+    while let Some(Event::Text(text)) = events.get(end) {
+        first_line.push_str(text);
+        end += 1;
+    }
+    let captures = CALLOUT_MARKER.captures(&first_line)?;
+    let kind = captures[1].to_lowercase();
+    let title = captures[2].trim().to_string();
+    match events.get(end) {
+        Some(Event::End(TagEnd::Paragraph)) => Some((kind, title, end + 1, false)),
+        Some(Event::SoftBreak) | Some(Event::HardBreak) => Some((kind, title, end + 1, true)),
+        _ => None,
+    }
+}
+
+fn gfm_kind_name(kind: BlockQuoteKind) -> &'static str {
+    match kind {
+        BlockQuoteKind::Note => "note",
+        BlockQuoteKind::Tip => "tip",
+        BlockQuoteKind::Important => "important",
+        BlockQuoteKind::Warning => "warning",
+        BlockQuoteKind::Caution => "caution",
+    }
+}
+
+fn callout_start(kind: &str, title: &str) -> Event<'static> {
+    let class = if KNOWN_TYPES.contains(&kind) {
+        format!("callout callout-{}", kind)
+    } else {
+        "callout".to_string()
+    };
+    let title = if title.is_empty() {
+        let mut chars = kind.chars();
+        match chars.next() {
+            Some(first) => first.to_uppercase().chain(chars).collect(),
+            None => String::new(),
+        }
+    } else {
+        title.to_string()
+    };
+    Event::Html(
+        format!(
+            r#"<div class="{}"><div class="callout-title">{}</div>"#,
+            class,
+            htmlescape::encode_minimal(&title)
+        )
+        .into(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pulldown_cmark::{Options, Parser, html};
+    use std::fs;
+
+    fn render(markdown: &str) -> String {
+        let events = render_callouts(Parser::new_ext(markdown, Options::ENABLE_GFM).collect());
+        let mut output = String::new();
+        html::push_html(&mut output, events.into_iter());
+        output
+    }
+
+    #[test]
+    fn gfm_alert_becomes_a_callout() {
+        let html = render("> [!WARNING]\n> Mind the gap.\n");
+        assert!(
+            html.starts_with(r#"<div class="callout callout-warning"><div class="callout-title">Warning</div>"#),
+            "{}",
+            html
+        );
+        assert!(html.contains("<p>Mind the gap.</p>"));
+        assert!(html.trim_end().ends_with("</div>"));
+        assert!(!html.contains("blockquote"));
+    }
+
+    #[test]
+    fn obsidian_marker_keeps_title_and_body() {
+        let html = render("> [!DANGER] Hot stuff\n> Do not touch.\n");
+        assert!(
+            html.contains(r#"<div class="callout callout-danger"><div class="callout-title">Hot stuff</div>"#),
+            "{}",
+            html
+        );
+        assert!(html.contains("<p>Do not touch.</p>"));
+        assert!(!html.contains("[!DANGER]"));
+    }
+
+    #[test]
+    fn unknown_types_get_a_generic_callout_and_plain_quotes_stay() {
+        let html = render("> [!RECIPE]\n> Flour.\n");
+        assert!(html.contains(r#"<div class="callout"><div class="callout-title">Recipe</div>"#), "{}", html);
+        assert_eq!(render("> Just a quote.\n"), "<blockquote>\n<p>Just a quote.</p>\n</blockquote>\n");
+    }
+
+    #[test]
+    fn setup_writes_the_minified_stylesheet() {
+        let dist_static = std::env::temp_dir().join(format!("sekiei-callouts-css-{}", std::process::id()));
+        fs::create_dir_all(&dist_static).unwrap();
+        let urls = setup_callouts(&dist_static).unwrap();
+        let url = &urls["callouts.css"];
+        let css = fs::read_to_string(dist_static.join(url.trim_start_matches("/static/"))).unwrap();
+        fs::remove_dir_all(&dist_static).unwrap();
+        assert!(css.contains(".callout-warning{"), "{}", css);
+        assert!(css.contains("border-left:4px solid var(--callout-color)"), "{}", css);
+    }
+}
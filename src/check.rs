@@ -0,0 +1,120 @@
+use crate::{
+    logger::{info, warn},
+    config::Config,
+    dates::parse_date,
+    frontmatter::extract_frontmatter,
+    markdown::link_snippets,
+    paths::{
+        ambiguous_wiki_links, init_file_cache, init_permalinks, link_target_key, process_paths,
+        route_for, route_key, set_flatten_static,
+    },
+    utils::is_not_hidden_dir,
+};
+use colored::Colorize;
+use std::collections::{BTreeMap, HashSet};
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use walkdir::WalkDir;
+
+/// Validates the configuration and every content file without rendering
+/// anything: frontmatter, dates, internal links, permalinks and wiki link
+/// ambiguity. Problems are listed per file and make the command fail.
+pub fn check(config_path: &Path) -> Result<(), Box<dyn Error>> {
+    let config = Config::load(config_path)?;
+    set_flatten_static(config.paths.flatten_static);
+    init_file_cache();
+
+    let mut problems: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    let mut pages = Vec::new();
+    let mut known_routes: HashSet<String> = HashSet::from([route_key("/")]);
+    for entry in WalkDir::new("content")
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(is_not_hidden_dir)
+        .filter_map(|e| e.ok())
+    {
+        let path = entry.path();
+        if entry.file_type().is_dir() {
+            if let Ok(relative) = path.strip_prefix("content") {
+                known_routes.insert(route_key(&relative.to_string_lossy().replace('\\', "/")));
+            }
+        } else if path.extension().and_then(|s| s.to_str()) == Some("md") {
+            pages.push((path.to_path_buf(), fs::read_to_string(path)?));
+        }
+    }
+
+    // Each page is parsed once, for its permalink and for the checks below.
+    let parsed: Vec<_> = pages.iter().map(|(_, content)| extract_frontmatter(content)).collect();
+    let frontmatters = pages
+        .iter()
+        .zip(&parsed)
+        .filter_map(|((path, _), parsed)| Some((path.as_path(), &parsed.as_ref().ok()?.0)));
+    if let Err(e) = init_permalinks(frontmatters) {
+        problems.entry("content/".to_string()).or_default().push(e.to_string());
+    }
+    known_routes.extend(pages.iter().map(|(path, _)| route_key(&route_for(path))));
+
+    for ((path, _), parsed) in pages.iter().zip(parsed) {
+        let file = path.display().to_string().replace('\\', "/");
+        let mut file_problems = Vec::new();
+
+        match parsed {
+            Err(e) => file_problems.push(e.to_string()),
+            Ok((frontmatter, md_content)) => {
+                if frontmatter.title.trim().is_empty() {
+                    file_problems.push("missing title".to_string());
+                }
+                if let Err(e) = parse_date(&frontmatter.date) {
+                    file_problems.push(format!("unparseable date '{}': {}", frontmatter.date, e));
+                }
+                if let Some(updated) = &frontmatter.updated
+                    && let Err(e) = parse_date(updated)
+                {
+                    file_problems.push(format!("unparseable updated date '{}': {}", updated, e));
+                }
+
+                for (name, candidates) in ambiguous_wiki_links(md_content) {
+                    let candidates: Vec<String> = candidates
+                        .iter()
+                        .map(|candidate| candidate.display().to_string().replace('\\', "/"))
+                        .collect();
+                    file_problems.push(format!(
+                        "ambiguous link [[{}]] matches {}",
+                        name,
+                        candidates.join(", ")
+                    ));
+                }
+
+                let processed = process_paths(md_content, path);
+                let mut reported = HashSet::new();
+                for (dest_url, _) in link_snippets(&processed) {
+                    if let Some(target) = link_target_key(&dest_url)
+                        && !known_routes.contains(&target)
+                        && reported.insert(dest_url.clone())
+                    {
+                        file_problems.push(format!("broken link to {}", dest_url));
+                    }
+                }
+            }
+        }
+
+        if !file_problems.is_empty() {
+            problems.entry(file).or_default().extend(file_problems);
+        }
+    }
+
+    if problems.is_empty() {
+        info!("{} {} pages", "No problems found in".green(), pages.len());
+        return Ok(());
+    }
+
+    let count: usize = problems.values().map(Vec::len).sum();
+    for (file, file_problems) in &problems {
+        warn!("{}", file.yellow());
+        for problem in file_problems {
+            warn!("  - {}", problem);
+        }
+    }
+    Err(format!("{} problem(s) found in {} file(s)", count, problems.len()).into())
+}
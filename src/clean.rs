@@ -0,0 +1,72 @@
+use crate::logger::info;
+use crate::config::{Config, PathsConfig};
+use crate::file_ops::{CACHE_DIR, PREVIOUS_DIR, STAGING_DIR, clear_directory_safely};
+use colored::Colorize;
+use std::error::Error;
+use std::path::Path;
+
+/// Removes the output directory set by the config at `config_path` (`dist/`
+/// when there is no config file), the build caches and whatever an
+/// interrupted build left behind.
+pub fn clean(config_path: &Path) -> Result<(), Box<dyn Error>> {
+    let output_dir = if config_path.exists() {
+        Config::load(config_path)?.paths.output_dir
+    } else {
+        PathsConfig::default().output_dir
+    };
+    let targets = [
+        Path::new(&output_dir),
+        Path::new(CACHE_DIR),
+        Path::new(STAGING_DIR),
+        Path::new(PREVIOUS_DIR),
+    ];
+    let mut removed_any = false;
+
+    for target in targets {
+        if clear_directory_safely(target, false)? {
+            removed_any = true;
+            info!(
+                "{} {}",
+                "Removed".green(),
+                target.display().to_string().replace('\\', "/").yellow()
+            );
+        }
+    }
+
+    if !removed_any {
+        info!("{}", "Nothing to clean.".yellow());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn removes_the_configured_output_and_build_dirs() {
+        let _lock = crate::utils::lock_global_state();
+        let dir = std::env::temp_dir().join(format!("sekiei-clean-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let generated = ["public_html", CACHE_DIR, STAGING_DIR, PREVIOUS_DIR];
+        for generated in generated.iter().chain(["dist", "content"].iter()) {
+            fs::create_dir_all(dir.join(generated)).unwrap();
+        }
+        let config = include_str!("../Config.example.toml")
+            .replace("output_dir = \"dist\"", "output_dir = \"public_html\"");
+        fs::write(dir.join("Config.toml"), config).unwrap();
+
+        let previous_dir = std::env::current_dir().unwrap();
+        std::env::set_current_dir(&dir).unwrap();
+        let result = clean(Path::new("Config.toml"));
+        std::env::set_current_dir(previous_dir).unwrap();
+        let left: Vec<bool> = generated.iter().map(|generated| dir.join(generated).exists()).collect();
+        let kept = dir.join("dist").exists() && dir.join("content").exists();
+        fs::remove_dir_all(&dir).unwrap();
+
+        result.unwrap();
+        assert_eq!(left, [false; 4], "{:?} should all be removed", generated);
+        assert!(kept, "only the configured output dir is removed");
+    }
+}
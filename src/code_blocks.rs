@@ -0,0 +1,46 @@
+use crate::logger::debug;
+use crate::file_ops::{static_url, write_hashed_file};
+use colored::Colorize;
+use css_minify::optimizations::{Level as CssLevel, Minifier as CssMinifier};
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::Path;
+
+/// Writes the hashed stylesheet controlling how long code lines behave and
+/// returns its URL keyed by unhashed file name. Blocks rendered with
+/// `code_wrap` get `<pre class="code-wrap">`, all others `code-scroll`.
+pub fn setup_code_blocks(dist_static: &Path) -> Result<HashMap<String, String>, Box<dyn Error>> {
+    let code_blocks_css = r#"
+.code-block pre.code-scroll {
+    overflow-x: auto;
+    white-space: pre;
+}
+
+.code-block pre.code-wrap {
+    white-space: pre-wrap;
+    overflow-wrap: anywhere;
+}
+
+.code-block pre.code-wrap code > span {
+    display: flex;
+}
+
+.code-block pre.code-wrap .line-number {
+    flex: none;
+}
+
+.code-block pre.code-wrap .code-line {
+    flex: 1;
+    min-width: 0;
+}
+"#;
+
+    let minified_css = CssMinifier::default().minify(code_blocks_css, CssLevel::Three)?;
+    let css_path = write_hashed_file(dist_static, "code_blocks.css", minified_css.as_bytes())?;
+
+    debug!("{}", "Generated and minified code_blocks.css".green());
+    Ok(HashMap::from([(
+        "code_blocks.css".to_string(),
+        static_url(dist_static, &css_path),
+    )]))
+}
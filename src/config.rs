@@ -1,5 +1,13 @@
+use crate::frontmatter::Frontmatter;
+use crate::sanitize::default_allowed_tags;
+use lazy_static::lazy_static;
+use regex::Regex;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
 use wildmatch::WildMatch;
 
 #[derive(Debug, PartialEq, Deserialize, Clone, Serialize)]
@@ -30,7 +38,31 @@ impl ThemeType {
 pub struct ThemeConfig {
     pub theme_type: ThemeType,
     pub preset: Option<String>,
+    pub preset_light: Option<String>,
+    pub preset_dark: Option<String>,
+    /// Preset the code-highlighting colors are taken from; defaults to the
+    /// UI palette's own.
+    pub syntax_preset: Option<String>,
     pub custom: Option<CustomTheme>,
+    /// Individual variables replaced in both the light and dark palettes.
+    #[serde(default)]
+    pub overrides: HashMap<String, String>,
+    #[serde(default)]
+    pub overrides_light: HashMap<String, String>,
+    #[serde(default)]
+    pub overrides_dark: HashMap<String, String>,
+}
+
+impl ThemeConfig {
+    /// Preset used for the light palette; `preset_light` wins over `preset`.
+    pub fn light_preset(&self) -> Option<&str> {
+        self.preset_light.as_deref().or(self.preset.as_deref())
+    }
+
+    /// Preset used for the dark palette; `preset_dark` wins over `preset`.
+    pub fn dark_preset(&self) -> Option<&str> {
+        self.preset_dark.as_deref().or(self.preset.as_deref())
+    }
 }
 
 #[derive(Deserialize, Debug, Serialize, Clone)]
@@ -38,12 +70,90 @@ pub struct GeneralConfig {
     pub base_url: String,
     pub title: String,
     pub description: String,
+    /// Subpath the site is served from, e.g. `/repo` for a GitHub Pages
+    /// project site. Prefixed onto root-relative links in rendered pages.
+    #[serde(default)]
+    pub base_path: String,
+    /// Default author of every page; a page's `author` frontmatter wins.
+    #[serde(default)]
+    pub author: Option<Author>,
+}
+
+/// Who wrote a page. Written either as just a name (`author = "Jane Doe"`)
+/// or as a table with a `name` and optional `url` and `email`.
+#[derive(Deserialize, Debug, Serialize, Clone, Default)]
+#[serde(from = "AuthorSpec")]
+pub struct Author {
+    pub name: String,
+    pub url: String,
+    pub email: String,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum AuthorSpec {
+    Name(String),
+    Full {
+        name: String,
+        #[serde(default)]
+        url: String,
+        #[serde(default)]
+        email: String,
+    },
+}
+
+impl From<AuthorSpec> for Author {
+    fn from(spec: AuthorSpec) -> Self {
+        match spec {
+            AuthorSpec::Name(name) => Author { name, ..Default::default() },
+            AuthorSpec::Full { name, url, email } => Author { name, url, email },
+        }
+    }
+}
+
+impl Author {
+    /// Every problem with this author; `email` ends up in RSS `<author>`,
+    /// which requires a plain address.
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.name.trim().is_empty() {
+            problems.push("Author configuration error: 'name' must not be empty".to_string());
+        }
+        if !self.email.is_empty() && !EMAIL.is_match(&self.email) {
+            problems.push(format!(
+                "Author configuration error: '{}' is not a valid email address",
+                self.email
+            ));
+        }
+        problems
+    }
+
+    /// The RSS `<author>` value, `email (name)`; RSS has no form without an
+    /// address.
+    pub fn rss_author(&self) -> Option<String> {
+        (!self.email.is_empty()).then(|| format!("{} ({})", self.email, self.name))
+    }
 }
 
 #[derive(Deserialize, Debug, Serialize, Clone)]
 pub struct CustomTheme {
-    pub light: HashMap<String, String>,
-    pub dark: HashMap<String, String>,
+    /// Named palettes, e.g. `[theme.custom.light]`, `[theme.custom.dark]`,
+    /// `[theme.custom.sepia]`. `light` and `dark` are required.
+    #[serde(flatten)]
+    pub themes: HashMap<String, HashMap<String, String>>,
+}
+
+impl CustomTheme {
+    /// Palettes in emission order: `light`, `dark`, then the rest by name.
+    pub fn ordered_themes(&self) -> Vec<(&String, &HashMap<String, String>)> {
+        let mut themes: Vec<_> = self.themes.iter().collect();
+        themes.sort_by_key(|(name, _)| match name.as_str() {
+            "light" => (0, name.as_str()),
+            "dark" => (1, name.as_str()),
+            _ => (2, name.as_str()),
+        });
+        themes
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -52,21 +162,130 @@ pub struct Images {
     pub quality: u8,
     #[serde(default)]
     pub compress_to_webp: bool,
+    #[serde(default)]
+    pub placeholder: PlaceholderConfig,
+    #[serde(default)]
+    pub lazy_mode: LazyMode,
+}
+
+/// How content images are deferred.
+#[derive(Debug, PartialEq, Deserialize, Clone, Serialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LazyMode {
+    /// Blurred placeholders swapped for the image by a script.
+    #[default]
+    Placeholder,
+    /// The browser's own `loading="lazy"`; no script or placeholders.
+    Native,
+    /// Images load normally.
+    Off,
 }
 
 impl Images {
-    pub fn validate(&self) -> Result<(), String> {
+    /// Every problem with the `[images]` settings.
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
         if self.quality != default_quality() && self.compress_to_webp {
-            return Err("Fields 'quality' and 'compress_to_webp' cannot be set at the same time in [images]".to_string());
+            problems.push("Fields 'quality' and 'compress_to_webp' cannot be set at the same time in [images]".to_string());
+        }
+        if self.placeholder.width == 0 {
+            problems.push("Field 'width' in [images.placeholder] must be greater than 0".to_string());
+        }
+        if self.placeholder.blur.is_nan() || self.placeholder.blur < 0.0 {
+            problems.push("Field 'blur' in [images.placeholder] must not be negative".to_string());
+        }
+        problems
+    }
+}
+
+/// Size and fidelity of the blurred lazy-loading placeholders.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PlaceholderConfig {
+    /// Width in pixels; the height keeps the image's aspect ratio.
+    #[serde(default = "default_placeholder_width")]
+    pub width: u32,
+    /// Gaussian blur sigma applied after resizing.
+    #[serde(default = "default_placeholder_blur")]
+    pub blur: f32,
+    /// JPEG quality of placeholders for JPEG images (WebP placeholders are
+    /// lossless).
+    #[serde(default = "default_placeholder_quality")]
+    pub quality: u8,
+}
+
+impl Default for PlaceholderConfig {
+    fn default() -> Self {
+        PlaceholderConfig {
+            width: default_placeholder_width(),
+            blur: default_placeholder_blur(),
+            quality: default_placeholder_quality(),
         }
-        Ok(())
     }
 }
 
+fn default_placeholder_width() -> u32 {
+    20
+}
+
+fn default_placeholder_blur() -> f32 {
+    3.0
+}
+
+fn default_placeholder_quality() -> u8 {
+    30
+}
+
 fn default_quality() -> u8 {
     100
 }
 
+/// `[giscus]` for one comment setup, or `[[giscus]]` entries (say, a repo
+/// per section); a page gets the first entry enabled for its route.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum GiscusConfig {
+    Single(Giscus),
+    Multiple(Vec<Giscus>),
+}
+
+impl Default for GiscusConfig {
+    fn default() -> Self {
+        GiscusConfig::Single(Giscus::default())
+    }
+}
+
+impl GiscusConfig {
+    pub fn entries(&self) -> &[Giscus] {
+        match self {
+            GiscusConfig::Single(giscus) => std::slice::from_ref(giscus),
+            GiscusConfig::Multiple(entries) => entries,
+        }
+    }
+
+    /// The configuration whose comments appear on `route`, if any.
+    pub fn for_route(&self, route: &str) -> Option<&Giscus> {
+        self.entries().iter().find(|giscus| giscus.is_enabled_for_route(route))
+    }
+
+    /// Every problem in any entry; with `[[giscus]]`, each is prefixed with
+    /// the entry's position.
+    pub fn problems(&self) -> Vec<String> {
+        match self {
+            GiscusConfig::Single(giscus) => giscus.problems(),
+            GiscusConfig::Multiple(entries) => entries
+                .iter()
+                .enumerate()
+                .flat_map(|(i, giscus)| {
+                    giscus
+                        .problems()
+                        .into_iter()
+                        .map(move |problem| format!("[[giscus]] entry {}: {}", i + 1, problem))
+                })
+                .collect(),
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Giscus {
     #[serde(default)]
@@ -80,41 +299,50 @@ pub struct Giscus {
     pub repo_id: Option<String>,
     pub category: Option<String>,
     pub category_id: Option<String>,
+    /// How giscus maps pages to discussions (`pathname`, `url`, `title`, ...).
+    #[serde(default = "default_giscus_mapping")]
+    pub mapping: String,
+    /// Fixed giscus theme; when unset the theme follows the site's light/dark toggle.
+    #[serde(default)]
+    pub theme: Option<String>,
+    #[serde(default = "default_giscus_lang")]
+    pub lang: String,
+}
+
+fn default_giscus_mapping() -> String {
+    "pathname".to_string()
+}
+
+fn default_giscus_lang() -> String {
+    "en".to_string()
 }
 
 impl Giscus {
-    pub fn validate(&self) -> Result<(), String> {
+    /// Every problem with the `[giscus]` settings.
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
         if !self.disabled_routes.is_empty() && !self.enabled_routes.is_empty() {
-            return Err("Giscus configuration error: 'disabled_routes' and 'enabled_routes' cannot both be specified at the same time".to_string());
+            problems.push("Giscus configuration error: 'disabled_routes' and 'enabled_routes' cannot both be specified at the same time".to_string());
         }
 
         if self.enable {
-            if self.repo.is_none() {
-                return Err(
-                    "Giscus configuration error: 'repo' is required when enable = true".to_string(),
-                );
-            }
-            if self.repo_id.is_none() {
-                return Err(
-                    "Giscus configuration error: 'repo_id' is required when enable = true"
-                        .to_string(),
-                );
-            }
-            if self.category.is_none() {
-                return Err(
-                    "Giscus configuration error: 'category' is required when enable = true"
-                        .to_string(),
-                );
-            }
-            if self.category_id.is_none() {
-                return Err(
-                    "Giscus configuration error: 'category_id' is required when enable = true"
-                        .to_string(),
-                );
+            let required = [
+                ("repo", self.repo.is_none()),
+                ("repo_id", self.repo_id.is_none()),
+                ("category", self.category.is_none()),
+                ("category_id", self.category_id.is_none()),
+            ];
+            for (field, missing) in required {
+                if missing {
+                    problems.push(format!(
+                        "Giscus configuration error: '{}' is required when enable = true",
+                        field
+                    ));
+                }
             }
         }
 
-        Ok(())
+        problems
     }
 
     pub fn is_enabled_for_route(&self, route: &str) -> bool {
@@ -143,15 +371,448 @@ pub struct Config {
     pub general: GeneralConfig,
     pub images: Images,
     #[serde(default)]
-    pub giscus: Giscus,
+    pub giscus: GiscusConfig,
+    #[serde(default)]
+    pub og: OgConfig,
+    #[serde(default)]
+    pub content: ContentConfig,
+    #[serde(default)]
+    pub build: BuildConfig,
+    #[serde(default)]
+    pub paths: PathsConfig,
+    #[serde(default)]
+    pub file_tree: FileTreeConfig,
+    #[serde(default)]
+    pub seo: SeoConfig,
+    #[serde(default)]
+    pub i18n: I18nConfig,
+    /// Extra redirects from an old path to a route or URL, handled like
+    /// frontmatter `aliases`.
+    #[serde(default)]
+    pub redirects: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct SeoConfig {
+    /// Full contents of `robots.txt`; a `static/robots.txt` takes precedence.
+    #[serde(default)]
+    pub robots: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct I18nConfig {
+    /// Language codes (e.g. `["en", "ja"]`) whose pages live in
+    /// `content/<code>/`. Pages outside those directories belong to the
+    /// first language. Empty turns translations off.
+    #[serde(default)]
+    pub languages: Vec<String>,
+}
+
+impl I18nConfig {
+    /// Every problem with the `[i18n]` settings.
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        for (i, language) in self.languages.iter().enumerate() {
+            if !LANGUAGE_TAG.is_match(language) {
+                problems.push(format!(
+                    "I18n configuration error: '{}' is not a language code such as 'en' or 'pt-BR'",
+                    language
+                ));
+            } else if self.languages[..i].contains(language) {
+                problems.push(format!("I18n configuration error: '{}' is listed twice in 'languages'", language));
+            }
+        }
+        problems
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FileTreeConfig {
+    /// Render the sidebar file tree and write its script and stylesheet.
+    #[serde(default = "default_true")]
+    pub enable: bool,
+    /// Wildmatch patterns for entries to leave out of the tree, matched
+    /// against both the file name and the path relative to `content/`.
+    #[serde(default)]
+    pub exclude: Vec<String>,
+}
+
+impl Default for FileTreeConfig {
+    fn default() -> Self {
+        FileTreeConfig {
+            enable: true,
+            exclude: Vec::new(),
+        }
+    }
+}
+
+impl FileTreeConfig {
+    pub fn is_excluded(&self, name: &str, relative_path: &str) -> bool {
+        self.exclude.iter().any(|pattern| {
+            let pattern = WildMatch::new(pattern);
+            pattern.matches(name) || pattern.matches(relative_path)
+        })
+    }
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PathsConfig {
+    /// Flatten content assets into `static/` (`blog/img/a.png` ->
+    /// `static/blog-img-a.png`) instead of mirroring their directories.
+    #[serde(default = "default_true")]
+    pub flatten_static: bool,
+    /// Directory copied verbatim to the root of `dist/` (for `CNAME`,
+    /// `favicon.ico`, `.well-known/` and the like).
+    #[serde(default = "default_public_dir")]
+    pub public_dir: String,
+    /// Where the site is written, `dist` by default.
+    #[serde(default = "default_output_dir")]
+    pub output_dir: String,
+}
+
+impl Default for PathsConfig {
+    fn default() -> Self {
+        PathsConfig {
+            flatten_static: true,
+            public_dir: default_public_dir(),
+            output_dir: default_output_dir(),
+        }
+    }
+}
+
+fn default_public_dir() -> String {
+    "public".to_string()
+}
+
+fn default_output_dir() -> String {
+    "dist".to_string()
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BuildConfig {
+    /// Add a content hash to the names of files copied from `static/`, as is
+    /// always done for generated CSS/JS.
+    #[serde(default)]
+    pub hash_static_files: bool,
+    /// Minify inline `<script>` contents of rendered pages. Event handler
+    /// attributes such as `onclick` are left untouched.
+    #[serde(default)]
+    pub minify_inline_js: bool,
+    /// Also list aliases and `[redirects]` in a `_redirects` file for hosts
+    /// such as Netlify or Cloudflare Pages.
+    #[serde(default)]
+    pub emit_redirects: bool,
+    /// Strip comments, metadata and editor attributes from SVGs in
+    /// `static/` and `content/`.
+    #[serde(default = "default_true")]
+    pub minify_svg: bool,
+    /// Wildmatch patterns (matched against file names and paths relative to
+    /// `static/`) for files copied verbatim, without minification or hashing.
+    #[serde(default)]
+    pub static_exclude: Vec<String>,
+    /// Write `.gz` and/or `.br` copies of text outputs next to the
+    /// originals, for hosts that serve pre-compressed files.
+    #[serde(default)]
+    pub precompress: Vec<Compression>,
+}
+
+impl Default for BuildConfig {
+    fn default() -> Self {
+        BuildConfig {
+            hash_static_files: false,
+            minify_inline_js: false,
+            emit_redirects: false,
+            minify_svg: true,
+            static_exclude: Vec::new(),
+            precompress: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    Gzip,
+    Brotli,
+}
+
+impl Compression {
+    /// Appended to the original file name, e.g. `index.html.gz`.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Compression::Gzip => "gz",
+            Compression::Brotli => "br",
+        }
+    }
+}
+
+impl BuildConfig {
+    /// Whether a `static/` file matches `static_exclude`, so it is copied
+    /// as is under its own name: no minification and no content hash.
+    pub fn excludes_static(&self, name: &str, relative_path: &str) -> bool {
+        self.static_exclude.iter().any(|pattern| {
+            let pattern = WildMatch::new(pattern);
+            pattern.matches(name) || pattern.matches(relative_path)
+        })
+    }
+
+    /// Whether a `static/` file should be copied without minification: it is
+    /// already minified (`*.min.js`, `*.min.css`) or excluded from
+    /// processing. Returns the reason for the log line.
+    pub fn skips_minification(&self, name: &str, relative_path: &str) -> Option<&'static str> {
+        if self.excludes_static(name, relative_path) {
+            return Some("matches static_exclude");
+        }
+        (name.ends_with(".min.js") || name.ends_with(".min.css")).then_some("already minified")
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ContentConfig {
+    /// Load KaTeX on content pages to render `$...$` and `$$...$$`.
+    #[serde(default)]
+    pub math: bool,
+    /// Filter raw HTML in markdown (`Event::Html`/`Event::InlineHtml`)
+    /// through `allowed_tags`, dropping scripts and event handlers.
+    #[serde(default)]
+    pub sanitize_html: bool,
+    #[serde(default = "default_allowed_tags")]
+    pub allowed_tags: Vec<String>,
+    #[serde(default)]
+    pub nav_scope: NavScope,
+    /// Soft-wrap long lines in code blocks instead of scrolling horizontally.
+    #[serde(default)]
+    pub code_wrap: bool,
+    /// Guess the language of fenced code blocks that have no language tag.
+    #[serde(default)]
+    pub autodetect_lang: bool,
+    /// Number the lines of code blocks; a block can override this with a
+    /// `lines` or `nolines` token after its language.
+    #[serde(default = "default_true")]
+    pub line_numbers: bool,
+    /// Leave task list checkboxes enabled (and numbered with
+    /// `data-task-index`) so client-side scripts can toggle them.
+    #[serde(default)]
+    pub interactive_tasks: bool,
+    /// Turn straight quotes and `--`/`---` into typographic ones in prose.
+    #[serde(default = "default_true")]
+    pub smart_punctuation: bool,
+    /// Append a `#` link to each heading pointing at its own id.
+    #[serde(default)]
+    pub heading_anchors: bool,
+    /// Mark links to other sites with an icon.
+    #[serde(default)]
+    pub external_link_icon: bool,
+}
+
+/// Which pages `prev_post`/`next_post` are picked from.
+#[derive(Debug, PartialEq, Deserialize, Clone, Serialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum NavScope {
+    #[default]
+    Site,
+    Section,
+}
+
+impl Default for ContentConfig {
+    fn default() -> Self {
+        ContentConfig {
+            math: false,
+            sanitize_html: false,
+            allowed_tags: default_allowed_tags(),
+            nav_scope: NavScope::default(),
+            code_wrap: false,
+            autodetect_lang: false,
+            line_numbers: true,
+            interactive_tasks: false,
+            smart_punctuation: true,
+            heading_anchors: false,
+            external_link_icon: false,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct OgConfig {
+    /// Render a title card per post when its frontmatter has no `image`.
+    #[serde(default)]
+    pub generate: bool,
+}
+
+/// Config file read when `--config` isn't given, relative to the current
+/// directory.
+pub const DEFAULT_CONFIG: &str = "Config.toml";
+
 impl Config {
+    /// Reads, parses and validates the site configuration at `path`, turning
+    /// the common first-run failures into actionable messages.
+    pub fn load(path: &Path) -> Result<Config, Box<dyn Error>> {
+        let config_str = match fs::read_to_string(path) {
+            Ok(s) => s,
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                return Err(format!(
+                    "No {} found in the current directory. Run sekiei from the site root, or create one \
+                     (see Config.example.toml) with at least the [general], [theme] and [images] sections.",
+                    path.display()
+                )
+                .into());
+            }
+            Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e).into()),
+        };
+        let config: Config = toml::from_str(&config_str).map_err(|e| {
+            match e.span() {
+                Some(span) => {
+                    let (line, column) = line_column(&config_str, span.start);
+                    format!(
+                        "Failed to parse {} at line {}, column {}: {}",
+                        path.display(),
+                        line,
+                        column,
+                        e.message()
+                    )
+                }
+                None => format!("Failed to parse {}: {}", path.display(), e.message()),
+            }
+        })?;
+        // Parsed once more with `${VAR}`s expanded; the first pass above is
+        // what reports syntax and type errors with their position.
+        let config = if config_str.contains("${") {
+            let mut table: toml::Value = toml::from_str(&config_str)?;
+            expand_env_vars(&mut table, "").map_err(|e| format!("{}: {}", path.display(), e))?;
+            table
+                .try_into()
+                .map_err(|e| format!("Failed to parse {} after expanding variables: {}", path.display(), e))?
+        } else {
+            config
+        };
+        config
+            .validate()
+            .map_err(|e| format!("Invalid configuration: {}", e))?;
+        Ok(config)
+    }
+
+    /// Replaces `base_url` and/or `base_path` (e.g. from `build --base-url`
+    /// for a preview deployment) and checks the result again.
+    pub fn override_urls(&mut self, base_url: Option<&str>, base_path: Option<&str>) -> Result<(), String> {
+        if let Some(base_url) = base_url {
+            self.general.base_url = base_url.to_string();
+        }
+        if let Some(base_path) = base_path {
+            self.general.base_path = base_path.to_string();
+        }
+        self.validate()
+            .map_err(|e| format!("Invalid configuration after command-line overrides: {}", e))
+    }
+
+    /// Absolute URL of the root-relative site `path`, under `base_path`:
+    /// `base_url` may be just the origin or already end with `base_path`.
+    pub fn site_url(&self, path: &str) -> String {
+        let base_url = self.general.base_url.trim_end_matches('/');
+        let base_path = self.general.base_path.trim_end_matches('/');
+        if base_url.ends_with(base_path) {
+            crate::utils::absolute_url(base_url, path)
+        } else {
+            crate::utils::absolute_url(&format!("{}{}", base_url, base_path), path)
+        }
+    }
+
+    /// Checks every section and reports all problems together.
     pub fn validate(&self) -> Result<(), String> {
-        self.images.validate()?;
-        self.giscus.validate()?;
-        Ok(())
+        let mut problems = self.images.problems();
+        problems.extend(self.giscus.problems());
+        problems.extend(self.i18n.problems());
+        if let Some(author) = &self.general.author {
+            problems.extend(author.problems());
+        }
+        let base_path = &self.general.base_path;
+        if !base_path.is_empty() && !base_path.starts_with('/') {
+            problems.push(format!(
+                "General configuration error: 'base_path' must start with '/', got '{}'",
+                base_path
+            ));
+        }
+        if let Err(e) = crate::theme::resolve_palettes(self) {
+            problems.push(format!("Theme configuration error: {}", e));
+        }
+
+        match problems.as_slice() {
+            [] => Ok(()),
+            [problem] => Err(problem.clone()),
+            _ => Err(format!("{} problems: {}", problems.len(), problems.join("; "))),
+        }
     }
+
+    /// The author of a page: its `author` frontmatter (a name or a table like
+    /// `[general] author`) if set, the site's author otherwise.
+    pub fn page_author(&self, frontmatter: &Frontmatter) -> Result<Option<Author>, String> {
+        let Some(author) = &frontmatter.author else {
+            return Ok(self.general.author.clone());
+        };
+        match author.problems().as_slice() {
+            [] => Ok(Some(author.clone())),
+            problems => Err(problems.join("; ")),
+        }
+    }
+}
+
+lazy_static! {
+    static ref LANGUAGE_TAG: Regex = Regex::new(r"^[A-Za-z]{2,3}(-[A-Za-z0-9]{2,8})*$").unwrap();
+    static ref EMAIL: Regex = Regex::new(r"^[^@\s()]+@[^@\s()]+\.[^@\s()]+$").unwrap();
+    static ref ENV_VAR: Regex = Regex::new(r"\$\{([A-Za-z_][A-Za-z0-9_]*)(?::-([^}]*))?\}").unwrap();
+}
+
+/// Replaces `${VAR}` in every string of the config with the environment
+/// variable's value, or with `default` in `${VAR:-default}` when the
+/// variable is unset or empty. A reference to an unset variable without a
+/// default is an error naming the key.
+fn expand_env_vars(value: &mut toml::Value, key: &str) -> Result<(), String> {
+    match value {
+        toml::Value::String(text) => {
+            let mut missing = None;
+            let expanded = ENV_VAR.replace_all(text, |caps: &regex::Captures| {
+                match (std::env::var(&caps[1]).ok().filter(|v| !v.is_empty()), caps.get(2)) {
+                    (Some(value), _) => value,
+                    (None, Some(default)) => default.as_str().to_string(),
+                    (None, None) => {
+                        missing.get_or_insert_with(|| caps[1].to_string());
+                        String::new()
+                    }
+                }
+            });
+            if let Some(name) = missing {
+                return Err(format!(
+                    "'{}' uses ${{{}}}, but that environment variable is not set",
+                    key, name
+                ));
+            }
+            *text = expanded.into_owned();
+        }
+        toml::Value::Array(items) => {
+            for (i, item) in items.iter_mut().enumerate() {
+                expand_env_vars(item, &format!("{}[{}]", key, i))?;
+            }
+        }
+        toml::Value::Table(table) => {
+            for (name, item) in table.iter_mut() {
+                let key = if key.is_empty() { name.clone() } else { format!("{}.{}", key, name) };
+                expand_env_vars(item, &key)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// 1-based line and column of a byte offset.
+fn line_column(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset.min(source.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().unwrap_or("").chars().count() + 1;
+    (line, column)
 }
 
 impl Default for Giscus {
@@ -164,6 +825,9 @@ impl Default for Giscus {
             repo_id: None,
             category: None,
             category_id: None,
+            mapping: default_giscus_mapping(),
+            theme: None,
+            lang: default_giscus_lang(),
         }
     }
 }
@@ -1205,3 +1869,50 @@ pub fn get_preset_themes() -> HashMap<String, (HashMap<String, String>, HashMap<
     .into_iter()
     .collect::<HashMap<_, _>>()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn giscus(routes: &str) -> Giscus {
+        toml::from_str(&format!(
+            "enable = true\nrepo = \"o/r\"\nrepo_id = \"R\"\ncategory = \"c\"\ncategory_id = \"C\"\n{}",
+            routes
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn giscus_enabled_routes_are_an_allowlist() {
+        let giscus = giscus("enabled_routes = [\"/blog/*\", \"/\"]");
+        assert!(giscus.is_enabled_for_route("/blog/first"));
+        assert!(giscus.is_enabled_for_route("/"));
+        assert!(!giscus.is_enabled_for_route("/notes/idea"));
+    }
+
+    #[test]
+    fn giscus_disabled_routes_are_a_denylist() {
+        let giscus = giscus("disabled_routes = [\"/notes/*\"]");
+        assert!(!giscus.is_enabled_for_route("/notes/idea"));
+        assert!(giscus.is_enabled_for_route("/blog/first"));
+        assert!(giscus.is_enabled_for_route("/"));
+    }
+
+    #[test]
+    fn giscus_off_matches_no_route() {
+        let mut giscus = giscus("");
+        assert!(giscus.is_enabled_for_route("/anything"));
+        giscus.enable = false;
+        assert!(!giscus.is_enabled_for_route("/anything"));
+    }
+
+    #[test]
+    fn first_matching_giscus_entry_wins() {
+        let config = GiscusConfig::Multiple(vec![
+            giscus("enabled_routes = [\"/blog/*\"]\nmapping = \"title\""),
+            giscus(""),
+        ]);
+        assert_eq!(config.for_route("/blog/first").unwrap().mapping, "title");
+        assert_eq!(config.for_route("/notes/idea").unwrap().mapping, "pathname");
+    }
+}
@@ -0,0 +1,53 @@
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use std::error::Error;
+
+const DATETIME_WITH_OFFSET_FORMATS: [&str; 4] = [
+    "%Y-%m-%d %H:%M:%S %z",
+    "%Y-%m-%d %H:%M %z",
+    "%Y-%m-%dT%H:%M:%S%z",
+    "%Y-%m-%dT%H:%M%z",
+];
+const DATETIME_FORMATS: [&str; 4] = [
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%d %H:%M",
+    "%Y-%m-%dT%H:%M:%S",
+    "%Y-%m-%dT%H:%M",
+];
+/// How listings show a date, e.g. `9 Jan 1984`.
+pub const DISPLAY_FORMAT: &str = "%-d %b %Y";
+
+const DATE_FORMATS: [&str; 5] = ["%d %b %Y", "%d %B %Y", "%Y-%m-%d", "%Y/%m/%d", "%d/%m/%Y"];
+
+/// Parses a frontmatter date. RFC 3339 and ISO 8601 style datetimes keep their
+/// time and offset (UTC when no offset is given); date-only values fall back
+/// to midnight UTC.
+pub fn parse_date(date_str: &str) -> Result<DateTime<FixedOffset>, Box<dyn Error>> {
+    let trimmed_date = date_str.trim();
+
+    if let Ok(date) = DateTime::parse_from_rfc3339(trimmed_date) {
+        return Ok(date);
+    }
+    for format in &DATETIME_WITH_OFFSET_FORMATS {
+        if let Ok(date) = DateTime::parse_from_str(trimmed_date, format) {
+            return Ok(date);
+        }
+    }
+    for format in &DATETIME_FORMATS {
+        if let Ok(naive) = NaiveDateTime::parse_from_str(trimmed_date, format) {
+            return Ok(Utc.from_utc_datetime(&naive).fixed_offset());
+        }
+    }
+    for format in &DATE_FORMATS {
+        if let Ok(naive_date) = NaiveDate::parse_from_str(trimmed_date, format) {
+            let midnight = naive_date.and_hms_opt(0, 0, 0).unwrap();
+            return Ok(Utc.from_utc_datetime(&midnight).fixed_offset());
+        }
+    }
+
+    Err(format!(
+        "Could not parse date '{}'. Expected a date such as '24 Jan 2025' or '2025-01-24', \
+         or a datetime such as '2025-01-24T14:30:00Z' or '2025-01-24 14:30 +0200'",
+        trimmed_date
+    )
+    .into())
+}
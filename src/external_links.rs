@@ -0,0 +1,71 @@
+use crate::logger::debug;
+use crate::file_ops::{static_url, write_hashed_file};
+use colored::Colorize;
+use css_minify::optimizations::{Level as CssLevel, Minifier as CssMinifier};
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::Path;
+
+const ICON: &str = r#"<span class="external-icon" aria-hidden="true"></span>"#;
+
+lazy_static! {
+    /// An `<a>` with an absolute http(s) `href`: the URL's host (with any
+    /// port) and the link's inner HTML.
+    static ref EXTERNAL_LINK: Regex =
+        Regex::new(r#"(?s)<a\s[^>]*?href="https?://([^/"?#]+)[^"]*"[^>]*>(.*?)</a>"#).unwrap();
+}
+
+/// Writes the hashed stylesheet for `[content] external_link_icon` and
+/// returns its URL keyed by unhashed file name.
+pub fn setup_external_links(dist_static: &Path) -> Result<HashMap<String, String>, Box<dyn Error>> {
+    let external_links_css = r#"
+.external-icon::after {
+    content: "\2197";
+    display: inline-block;
+    margin-left: 0.15em;
+    font-size: 0.75em;
+    vertical-align: super;
+    line-height: 1;
+}
+"#;
+
+    let minified_css = CssMinifier::default().minify(external_links_css, CssLevel::Three)?;
+    let css_path = write_hashed_file(dist_static, "external_links.css", minified_css.as_bytes())?;
+
+    debug!("{}", "Generated and minified external_links.css".green());
+    Ok(HashMap::from([(
+        "external_links.css".to_string(),
+        static_url(dist_static, &css_path),
+    )]))
+}
+
+/// Puts an icon after every link to a host other than `base_url`'s. Links
+/// wrapping an image or an icon of their own, and ones already followed by
+/// the icon, are left alone.
+pub fn mark_external_links(html: &str, base_url: &str) -> String {
+    let site_host = host_of(base_url);
+    let mut marked = String::with_capacity(html.len());
+    let mut last = 0;
+    for link in EXTERNAL_LINK.captures_iter(html) {
+        let whole = link.get(0).unwrap();
+        marked.push_str(&html[last..whole.end()]);
+        last = whole.end();
+
+        let host = &link[1];
+        let inner = &link[2];
+        let has_icon = ["<img", "<svg", "external-icon"].iter().any(|icon| inner.contains(icon))
+            || html[whole.end()..].starts_with(ICON);
+        if !host.eq_ignore_ascii_case(site_host) && !has_icon {
+            marked.push_str(ICON);
+        }
+    }
+    marked.push_str(&html[last..]);
+    marked
+}
+
+fn host_of(url: &str) -> &str {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    rest.split(['/', '?', '#']).next().unwrap_or_default()
+}
@@ -1,10 +1,76 @@
-use std::{error::Error, fs, path::Path};
+use crate::utils::content_hash;
+use std::{
+    error::Error,
+    fs, io,
+    path::{Path, PathBuf},
+};
+use walkdir::WalkDir;
 
-pub fn clear_directory_safely(path: &Path) -> std::io::Result<()> {
-    if path.exists() {
+pub const CACHE_DIR: &str = ".sekiei-cache";
+/// Full builds render here and replace `dist` only once they succeed.
+pub const STAGING_DIR: &str = ".sekiei-staging";
+/// The output a finished build replaces, kept only while it is swapped out.
+pub const PREVIOUS_DIR: &str = ".sekiei-previous";
+
+/// Removes `path` if it exists and, with `recreate`, creates it again empty.
+/// Returns whether there was anything to remove.
+pub fn clear_directory_safely(path: &Path, recreate: bool) -> std::io::Result<bool> {
+    let existed = path.exists();
+    if existed {
         std::fs::remove_dir_all(path)?;
     }
-    std::fs::create_dir(path)?;
+    if recreate {
+        std::fs::create_dir(path)?;
+    }
+    Ok(existed)
+}
+
+/// Replaces `target` with the finished `staging` directory. Both are renamed
+/// so the old output stays complete until the new one is in place; if a
+/// rename fails (e.g. `target` is held open on Windows) it falls back to
+/// clearing `target` and moving the staged entries into it.
+pub fn swap_directory(staging: &Path, target: &Path) -> io::Result<()> {
+    let previous = Path::new(PREVIOUS_DIR);
+    clear_directory_safely(previous, false)?;
+    if target.exists() && fs::rename(target, previous).is_err() {
+        return move_contents(staging, target);
+    }
+    match fs::rename(staging, target) {
+        Ok(()) => {
+            clear_directory_safely(previous, false)?;
+            Ok(())
+        }
+        Err(_) => {
+            if previous.exists() {
+                fs::rename(previous, target)?;
+            }
+            move_contents(staging, target)
+        }
+    }
+}
+
+fn move_contents(staging: &Path, target: &Path) -> io::Result<()> {
+    clear_directory_safely(target, true)?;
+    for entry in fs::read_dir(staging)? {
+        let entry = entry?;
+        let destination = target.join(entry.file_name());
+        if fs::rename(entry.path(), &destination).is_err() {
+            copy_recursively(&entry.path(), &destination)?;
+        }
+    }
+    fs::remove_dir_all(staging)
+}
+
+fn copy_recursively(source: &Path, destination: &Path) -> io::Result<()> {
+    for entry in WalkDir::new(source).into_iter().filter_map(|e| e.ok()) {
+        let relative = entry.path().strip_prefix(source).unwrap_or(entry.path());
+        let output = destination.join(relative);
+        if entry.file_type().is_dir() {
+            create_directory_safely(&output)?;
+        } else {
+            fs::copy(entry.path(), &output)?;
+        }
+    }
     Ok(())
 }
 
@@ -19,3 +85,24 @@ pub fn safely_write_file(path: &Path, content: &str) -> Result<(), Box<dyn Error
     fs::write(path, content)?;
     Ok(())
 }
+
+/// Writes `content` under `dir` as `<stem>.<hash>.<ext>` and returns the path
+/// written, so browsers never serve a stale cached copy after a deploy.
+pub fn write_hashed_file(dir: &Path, name: &str, content: &[u8]) -> Result<PathBuf, Box<dyn Error>> {
+    let name_path = Path::new(name);
+    let stem = name_path.file_stem().unwrap_or_default().to_string_lossy();
+    let hash = content_hash(content);
+    let hashed_name = match name_path.extension() {
+        Some(ext) => format!("{}.{}.{}", stem, hash, ext.to_string_lossy()),
+        None => format!("{}.{}", stem, hash),
+    };
+    let path = dir.join(hashed_name);
+    fs::write(&path, content)?;
+    Ok(path)
+}
+
+/// URL a file written under `dist/static` is served at.
+pub fn static_url(dist_static: &Path, path: &Path) -> String {
+    let relative = path.strip_prefix(dist_static).unwrap_or(path);
+    format!("/static/{}", relative.to_string_lossy().replace('\\', "/"))
+}
@@ -1,34 +1,71 @@
 use crate::{
-    file_ops::safely_write_file,
+    logger::debug,
+    file_ops::{static_url, write_hashed_file},
     utils::is_not_hidden_dir,
     config::Config,
+    paths::route_for,
 };
 use colored::Colorize;
 use std::{
+    collections::HashMap,
     error::Error,
     path::Path,
-    fs::File,
-    io::{BufRead, BufReader},
 };
 use walkdir::WalkDir;
 use minify_js::{Session, TopLevelMode, minify as js_minify};
 use css_minify::optimizations::{Level as CssLevel, Minifier as CssMinifier};
 use serde::{Deserialize, Serialize};
 
-pub fn process_file_tree_assets(dist_static: &Path) -> Result<(), Box<dyn Error>> {
+/// Writes the hashed file tree script and stylesheet and returns their URLs
+/// keyed by unhashed file name.
+pub fn process_file_tree_assets(dist_static: &Path) -> Result<HashMap<String, String>, Box<dyn Error>> {
     let js_content = r#"
+const STORAGE_PREFIX = 'sekiei:file-tree:';
+
+function setFolderOpen(folder, open) {
+    const ul = folder.querySelector(':scope > .folder-contents');
+    const icon = folder.querySelector(':scope > .folder-label .toggle-icon');
+    if (!ul) return;
+    ul.classList.toggle('hidden', !open);
+    if (icon) icon.classList.toggle('rotate-90', open);
+}
+
+function saveFolderState(folder, open) {
+    try {
+        localStorage.setItem(STORAGE_PREFIX + folder.dataset.path, open ? '1' : '0');
+    } catch (e) {}
+}
+
 document.addEventListener('DOMContentLoaded', () => {
-    const toggles = document.querySelectorAll('.file-tree .folder-label');
-    
-    toggles.forEach(toggle => {
+    const folders = document.querySelectorAll('.file-tree .directory');
+
+    folders.forEach(folder => {
+        let saved = null;
+        try {
+            saved = localStorage.getItem(STORAGE_PREFIX + folder.dataset.path);
+        } catch (e) {}
+        // Folders leading to the current page always start open.
+        if (saved !== null && !folder.classList.contains('active-path')) {
+            setFolderOpen(folder, saved === '1');
+        }
+
+        const toggle = folder.querySelector(':scope > .folder-label');
         toggle.addEventListener('click', (e) => {
             e.preventDefault();
-            const ul = toggle.nextElementSibling;
-            if (ul) {
-                ul.classList.toggle('hidden');
-                const icon = toggle.querySelector('.toggle-icon');
-                icon.classList.toggle('rotate-90');
-            }
+            const ul = folder.querySelector(':scope > .folder-contents');
+            const open = ul.classList.contains('hidden');
+            setFolderOpen(folder, open);
+            saveFolderState(folder, open);
+        });
+    });
+
+    document.querySelectorAll('.file-tree [data-tree-action]').forEach(button => {
+        button.addEventListener('click', () => {
+            const open = button.dataset.treeAction === 'expand';
+            folders.forEach(folder => {
+                setFolderOpen(folder, open);
+                saveFolderState(folder, open);
+            });
         });
     });
 });
@@ -67,6 +104,16 @@ document.addEventListener('DOMContentLoaded', () => {
 .toggle-icon {
     display: inline-block;
 }
+
+.file-tree-controls {
+    display: flex;
+    gap: 0.5rem;
+    margin-bottom: 0.5rem;
+}
+
+.file-tree-controls button {
+    cursor: pointer;
+}
 "#;
 
     let mut minified_js = Vec::new();
@@ -77,15 +124,18 @@ document.addEventListener('DOMContentLoaded', () => {
         js_content.as_bytes(),
         &mut minified_js,
     ).expect("Failed to minify file_tree.js");
-    safely_write_file(&dist_static.join("file_tree.js"), std::str::from_utf8(&minified_js)?)?;
+    let js_path = write_hashed_file(dist_static, "file_tree.js", &minified_js)?;
     
     let minified_css = CssMinifier::default()
         .minify(css_content, CssLevel::Three)
         .expect("Failed to minify file_tree.css");
-    safely_write_file(&dist_static.join("file_tree.css"), &minified_css)?;
+    let css_path = write_hashed_file(dist_static, "file_tree.css", minified_css.as_bytes())?;
 
-    println!("{}", "Generated and minified file_tree.js and file_tree.css".green());
-    Ok(())
+    debug!("{}", "Generated and minified file_tree.js and file_tree.css".green());
+    Ok(HashMap::from([
+        ("file_tree.js".to_string(), static_url(dist_static, &js_path)),
+        ("file_tree.css".to_string(), static_url(dist_static, &css_path)),
+    ]))
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -94,39 +144,58 @@ pub struct FileNode {
     pub path: String,
     pub is_dir: bool,
     pub children: Vec<FileNode>,
+    #[serde(default)]
+    pub weight: Option<i64>,
 }
 
-pub fn generate_file_tree_html(config: &Config, current_route: &str) -> Result<String, Box<dyn Error>> {
-    let nodes = build_file_tree(Path::new("content"), Path::new(""), config);
-    
+/// Renders an already built tree, marking the node for `current_route` and
+/// expanding the directories that lead to it.
+pub fn render_file_tree(nodes: &[FileNode], current_route: &str) -> String {
+    if nodes.is_empty() {
+        return String::new();
+    }
+    let current = current_route.trim_matches('/');
     let mut html = String::new();
-    html.push_str("<div class=\"file-tree\">\n<ul>\n");
+    html.push_str(
+        "<div class=\"file-tree\">\n\
+         <div class=\"file-tree-controls\">\n\
+         <button type=\"button\" data-tree-action=\"expand\">Expand all</button>\n\
+         <button type=\"button\" data-tree-action=\"collapse\">Collapse all</button>\n\
+         </div>\n<ul>\n",
+    );
     for node in nodes {
-        html.push_str(&render_file_node(&node, current_route));
+        html.push_str(&render_file_node(node, current));
     }
     html.push_str("</ul>\n</div>");
-    Ok(html)
+    html
+}
+
+/// Whether `route` (without surrounding slashes) is `node` or lies inside it.
+fn contains_route(node: &FileNode, route: &str) -> bool {
+    let path = node.path.trim_matches('/');
+    if path == route {
+        return true;
+    }
+    node.is_dir
+        && (route.starts_with(&format!("{}/", path))
+            || node.children.iter().any(|child| contains_route(child, route)))
 }
 
-fn render_file_node(node: &FileNode, current_route: &str) -> String {
+fn render_file_node(node: &FileNode, current: &str) -> String {
     let mut html = String::new();
-    let is_current = node.path == current_route || 
-                    (current_route == "/" && node.path == "") ||
-                    (node.is_dir && current_route.starts_with(&format!("/{}", node.path)));
-    
+    let is_current = node.path.trim_matches('/') == current;
+
     if node.is_dir {
-        let is_expanded = is_current || node.children.iter().any(|child| {
-            current_route == child.path || 
-            (current_route == "/" && child.path == "") ||
-            current_route.starts_with(&format!("/{}", child.path))
-        });
+        let is_expanded = contains_route(node, current);
         
         html.push_str(&format!(
-            "<li class=\"directory mb-1\">\n\
+            "<li class=\"directory mb-1 {}\" data-path=\"{}\">\n\
              <div class=\"folder-label flex items-center cursor-pointer text-neutral-600 dark:text-neutral-200 py-1\">\n\
              <span class=\"toggle-icon transform transition-transform duration-200 mr-1 {}\"><i class=\"ph filetree-icon filetree-folder ph-caret-right\"></i></span>\n\
              <span class=\"folder-name text-sm {}\">{}</span>\n\
              </div>\n",
+            if is_expanded { "active-path" } else { "" },
+            htmlescape::encode_attribute(&node.path),
             if is_expanded { "rotate-90" } else { "" },
             if is_current { "font-bold" } else { "" },
             node.name
@@ -136,7 +205,7 @@ fn render_file_node(node: &FileNode, current_route: &str) -> String {
             if is_expanded { "" } else { "hidden" }
         ));
         for child in &node.children {
-            html.push_str(&render_file_node(child, current_route));
+            html.push_str(&render_file_node(child, current));
         }
         html.push_str("</ul>\n</li>\n");
     } else {
@@ -169,13 +238,26 @@ fn render_file_node(node: &FileNode, current_route: &str) -> String {
     html
 }
 
-pub fn build_file_tree(base: &Path, relative: &Path, config: &Config) -> Vec<FileNode> {
+/// Walks `content/` into a tree. Page names come from `titles` (frontmatter
+/// titles keyed by route without the leading slash), falling back to the file
+/// stem, so no markdown file is opened here; a page missing from `titles` is
+/// left out. Nodes with a weight in `weights`
+/// (keyed the same way; a directory's comes from its `index.md`) sort first,
+/// lowest first, then directories before files, by name.
+pub fn build_file_tree(
+    base: &Path,
+    relative: &Path,
+    config: &Config,
+    titles: &HashMap<String, String>,
+    weights: &HashMap<String, i64>,
+) -> Vec<FileNode> {
     let full_path = base.join(relative);
     let mut nodes = Vec::new();
 
     for entry in WalkDir::new(&full_path)
         .min_depth(1)
-        .max_depth(1) 
+        .max_depth(1)
+        .sort_by_file_name()
         .into_iter()
         .filter_entry(is_not_hidden_dir)
         .filter_map(|e| e.ok())
@@ -195,16 +277,22 @@ pub fn build_file_tree(base: &Path, relative: &Path, config: &Config) -> Vec<Fil
         let rel_path = relative.join(&file_name);
         let path_str = rel_path.to_string_lossy().replace('\\', "/");
 
+        if config.file_tree.is_excluded(&file_name, &path_str) {
+            continue;
+        }
+
         if is_dir {
-            let children = build_file_tree(base, &rel_path, config);
+            let children = build_file_tree(base, &rel_path, config, titles, weights);
+            let index_route = route_for(&path.join("index.md"));
             nodes.push(FileNode {
                 name: file_name,
                 path: path_str,
                 is_dir,
                 children,
+                weight: weights.get(index_route.trim_start_matches('/')).copied(),
             });
         } else {
-            let mut name = file_name.clone();
+            let name;
             let mut final_path;
 
             if path.extension().map_or(false, |ext| ext == "md") {
@@ -214,49 +302,12 @@ pub fn build_file_tree(base: &Path, relative: &Path, config: &Config) -> Vec<Fil
                     .to_string_lossy()
                     .to_string();
 
-                if let Ok(file) = File::open(&path) {
-                    let reader = BufReader::new(file);
-                    let mut in_frontmatter = false;
-                    let mut found_title = false;
-
-                    for line in reader.lines().filter_map(Result::ok) {
-                        let trimmed_line = line.trim();
-                        if trimmed_line == "---" {
-                            if in_frontmatter {
-                                break;
-                            } else {
-                                in_frontmatter = true;
-                                continue;
-                            }
-                        }
-                        if in_frontmatter {
-                            if let Some((key, value)) = trimmed_line.split_once(':') {
-                                let key = key.trim();
-                                if key == "title" {
-                                    name = value.trim().to_string();
-                                    found_title = true;
-                                    break;
-                                }
-                            }
-                        }
-                    }
-                    if !found_title || name.is_empty() {
-                        name = default_name.clone();
-                    }
-                } else {
-                    name = default_name.clone();
-                }
-
-                final_path = if path_str.ends_with(".md") {
-                    let trimmed = &path_str[..path_str.len() - 3];
-                    if trimmed == "index" && relative.as_os_str().is_empty() {
-                        "".to_string()
-                    } else {
-                        trimmed.to_string()
-                    }
-                } else {
-                    path_str.clone()
+                final_path = route_for(path).trim_start_matches('/').to_string();
+                // Pages without an entry were left out of the build.
+                let Some(title) = titles.get(&final_path) else {
+                    continue;
                 };
+                name = if title.is_empty() { default_name } else { title.clone() };
             } else {
                 name = path
                     .file_stem()
@@ -264,7 +315,7 @@ pub fn build_file_tree(base: &Path, relative: &Path, config: &Config) -> Vec<Fil
                     .to_string_lossy()
                     .to_string();
                     
-                final_path = format!("static/{}", crate::utils::sanitize_filename(&path_str));
+                final_path = format!("static/{}", crate::paths::static_asset_name(&path_str));
                 if config.images.compress_to_webp {
                     if path.extension().map_or(false, |ext| {
                         ext == "jpg" || ext == "jpeg" || ext == "png"
@@ -276,20 +327,61 @@ pub fn build_file_tree(base: &Path, relative: &Path, config: &Config) -> Vec<Fil
                 }
             }
 
+            let weight = weights.get(&final_path).copied();
             nodes.push(FileNode {
                 name,
                 path: final_path,
                 is_dir,
                 children: Vec::new(),
+                weight,
             });
         }
     }
 
-    nodes.sort_by(|a, b| match (a.is_dir, b.is_dir) {
-        (true, false) => std::cmp::Ordering::Less,
-        (false, true) => std::cmp::Ordering::Greater,
-        _ => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+    sort_nodes(&mut nodes);
+    nodes
+}
+
+/// Weighted nodes first, lowest weight first; then directories before
+/// files; then by name, ignoring case.
+fn sort_nodes(nodes: &mut [FileNode]) {
+    nodes.sort_by(|a, b| {
+        (a.weight.is_none(), a.weight)
+            .cmp(&(b.weight.is_none(), b.weight))
+            .then_with(|| match (a.is_dir, b.is_dir) {
+                (true, false) => std::cmp::Ordering::Less,
+                (false, true) => std::cmp::Ordering::Greater,
+                _ => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+            })
     });
+}
 
-    nodes
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(name: &str, is_dir: bool, weight: Option<i64>) -> FileNode {
+        FileNode {
+            name: name.to_string(),
+            path: name.to_string(),
+            is_dir,
+            children: Vec::new(),
+            weight,
+        }
+    }
+
+    #[test]
+    fn weighted_nodes_come_first_then_directories_then_names() {
+        let mut nodes = vec![
+            node("b", true, None),
+            node("a", false, None),
+            node("Z", false, Some(2)),
+            node("y", true, Some(1)),
+            node("c", false, Some(1)),
+            node("C", true, None),
+        ];
+        sort_nodes(&mut nodes);
+        let names: Vec<&str> = nodes.iter().map(|node| node.name.as_str()).collect();
+        assert_eq!(names, ["y", "c", "Z", "b", "C", "a"]);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,52 @@
+use crate::{dates::parse_date, utils::slugify};
+use chrono::DateTime;
+use std::collections::HashMap;
+use std::fmt::Write;
+use tera::{Tera, Value};
+
+/// Registers sekiei's template filters, replacing Tera's `date`:
+///
+/// - `date(format="%B %Y")` reformats a frontmatter date in any format the
+///   site accepts (or a Unix timestamp); `format` defaults to `%Y-%m-%d`.
+/// - `slugify` turns text into the same slug headings get as their `id`.
+pub fn register_filters(tera: &mut Tera) {
+    tera.register_filter("date", date_filter);
+    tera.register_filter("slugify", slugify_filter);
+}
+
+fn date_filter(value: &Value, args: &HashMap<String, Value>) -> tera::Result<Value> {
+    let format = match args.get("format") {
+        Some(format) => format
+            .as_str()
+            .ok_or_else(|| tera::Error::msg("Filter `date`: `format` must be a string"))?,
+        None => "%Y-%m-%d",
+    };
+    let date = match value {
+        Value::String(date) => {
+            parse_date(date).map_err(|e| tera::Error::msg(format!("Filter `date`: {}", e)))?
+        }
+        Value::Number(timestamp) => timestamp
+            .as_i64()
+            .and_then(|timestamp| DateTime::from_timestamp(timestamp, 0))
+            .map(|date| date.fixed_offset())
+            .ok_or_else(|| tera::Error::msg(format!("Filter `date`: invalid timestamp {}", timestamp)))?,
+        _ => {
+            return Err(tera::Error::msg(format!(
+                "Filter `date` expects a date string or timestamp, got {}",
+                value
+            )));
+        }
+    };
+
+    let mut formatted = String::new();
+    write!(formatted, "{}", date.format(format))
+        .map_err(|_| tera::Error::msg(format!("Filter `date`: invalid format '{}'", format)))?;
+    Ok(Value::String(formatted))
+}
+
+fn slugify_filter(value: &Value, _args: &HashMap<String, Value>) -> tera::Result<Value> {
+    let text = value
+        .as_str()
+        .ok_or_else(|| tera::Error::msg(format!("Filter `slugify` expects a string, got {}", value)))?;
+    Ok(Value::String(slugify(text)))
+}
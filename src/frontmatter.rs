@@ -0,0 +1,278 @@
+use crate::config::Author;
+use serde::{Deserialize, Deserializer, de::Error as _};
+use serde_yaml::Value as YamlValue;
+use std::collections::HashMap;
+use std::error::Error;
+
+/// A page's frontmatter, whichever format it was written in.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Frontmatter {
+    pub title: String,
+    /// As written, e.g. `2024-01-09` or `2024-01-09T10:00:00Z`; see
+    /// `dates::parse_date`.
+    pub date: String,
+    /// When the page last changed meaningfully, in the same formats as
+    /// `date`; see `updated()`.
+    #[serde(default)]
+    pub updated: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// `tags: [a, b]` or `tags: "a, b"`.
+    #[serde(default, deserialize_with = "tags")]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub draft: bool,
+    /// Template from `templates/` to render with instead of `content.tera`.
+    #[serde(default)]
+    pub template: Option<String>,
+    #[serde(default)]
+    pub permalink: Option<String>,
+    #[serde(default)]
+    pub slug: Option<String>,
+    /// Old URLs redirecting here; one or a list.
+    #[serde(default, deserialize_with = "aliases")]
+    pub aliases: Vec<String>,
+    /// Palette the page is always shown with.
+    #[serde(default)]
+    pub theme: Option<String>,
+    #[serde(default)]
+    pub image: Option<String>,
+    #[serde(default, deserialize_with = "author")]
+    pub author: Option<Author>,
+    /// Name of the series the page is a part of.
+    #[serde(default)]
+    pub series: Option<String>,
+    /// Position in the series; parts without one follow, oldest first.
+    #[serde(default)]
+    pub series_order: Option<i64>,
+    /// Sort key in listings and the file tree; lower comes first, and pages
+    /// without one follow in the usual order.
+    #[serde(default)]
+    pub weight: Option<i64>,
+    /// Every other field, e.g. ones only a custom template reads.
+    #[serde(flatten)]
+    pub extra: HashMap<String, YamlValue>,
+    /// The whole frontmatter, as templates see it.
+    #[serde(skip)]
+    pub raw: YamlValue,
+}
+
+impl Frontmatter {
+    /// `updated`, or `date` for a page that was never updated.
+    pub fn updated(&self) -> &str {
+        self.updated.as_deref().unwrap_or(&self.date)
+    }
+}
+
+/// Optional fields that must be strings when set.
+const STRING_FIELDS: [&str; 8] = [
+    "updated",
+    "description",
+    "template",
+    "permalink",
+    "slug",
+    "theme",
+    "image",
+    "series",
+];
+/// Optional fields that must be integers when set.
+const INTEGER_FIELDS: [&str; 2] = ["series_order", "weight"];
+
+fn tags<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    let tags: Vec<String> = match Option::<YamlValue>::deserialize(deserializer)? {
+        Some(YamlValue::Sequence(tags)) => tags
+            .iter()
+            .filter_map(|tag| tag.as_str())
+            .map(|tag| tag.trim().to_string())
+            .collect(),
+        Some(YamlValue::String(tags)) => tags.split(',').map(|tag| tag.trim().to_string()).collect(),
+        _ => Vec::new(),
+    };
+    Ok(tags.into_iter().filter(|tag| !tag.is_empty()).collect())
+}
+
+fn aliases<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    Ok(match Option::<YamlValue>::deserialize(deserializer)? {
+        Some(YamlValue::Sequence(aliases)) => aliases
+            .iter()
+            .filter_map(|alias| alias.as_str())
+            .map(|alias| alias.to_string())
+            .collect(),
+        Some(YamlValue::String(alias)) => vec![alias],
+        _ => Vec::new(),
+    })
+}
+
+fn author<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Author>, D::Error> {
+    match Option::<YamlValue>::deserialize(deserializer)? {
+        None | Some(YamlValue::Null) => Ok(None),
+        Some(value) => serde_yaml::from_value(value).map(Some).map_err(|_| {
+            D::Error::custom("'author' must be a name or a mapping with 'name' and optional 'url' and 'email'")
+        }),
+    }
+}
+
+/// Rewrites a frontmatter date into the plain string form the rest of the
+/// pipeline reads, accepting quoted and unquoted dates as well as explicitly
+/// tagged ones (`!!timestamp 2024-01-09`).
+fn normalize_date(date: &mut YamlValue) -> Result<(), Box<dyn Error>> {
+    let normalized = match &*date {
+        YamlValue::String(_) => return Ok(()),
+        YamlValue::Tagged(tagged) => match &tagged.value {
+            YamlValue::String(s) => s.clone(),
+            _ => return Err("Date must be a string or a YAML timestamp".into()),
+        },
+        _ => return Err("Date must be a string or a YAML timestamp".into()),
+    };
+    *date = YamlValue::String(normalized.trim().to_string());
+    Ok(())
+}
+
+/// Splits a page into its frontmatter and markdown. The frontmatter is YAML
+/// between `---` lines, TOML between `+++` lines (as in Hugo) or a JSON
+/// object at the very start, and must have a string `title` and a `date`.
+pub fn extract_frontmatter(content: &str) -> Result<(Frontmatter, &str), Box<dyn Error>> {
+    let trimmed_content = content.trim_start();
+    let (mut frontmatter, md_content) = if trimmed_content.starts_with('{') {
+        // Padded like the fenced formats, so error locations refer to lines
+        // of the whole file.
+        let padded = padded(content, trimmed_content);
+        let mut values = serde_json::Deserializer::from_str(&padded).into_iter::<serde_json::Value>();
+        let frontmatter = match values.next() {
+            Some(value) => value.map_err(|e| format!("Invalid JSON in frontmatter: {}", e))?,
+            None => return Err("Frontmatter is missing".into()),
+        };
+        let end = values.byte_offset() - (padded.len() - trimmed_content.len());
+        (serde_yaml::to_value(frontmatter)?, &trimmed_content[end..])
+    } else if let Some(rest) = trimmed_content.strip_prefix("+++") {
+        let (frontmatter_str, md_content) = fenced(rest, "\n+++")?;
+        let frontmatter: toml::Value = toml::from_str(&padded(content, frontmatter_str))
+            .map_err(|e| format!("Invalid TOML in frontmatter: {}", e))?;
+        (toml_to_yaml(frontmatter), md_content)
+    } else if let Some(rest) = trimmed_content.strip_prefix("---") {
+        let (frontmatter_str, md_content) = fenced(rest, "\n---")?;
+        let frontmatter: YamlValue = serde_yaml::from_str(&padded(content, frontmatter_str))
+            .map_err(|e| format!("Invalid YAML in frontmatter: {}", e))?;
+        (frontmatter, md_content)
+    } else {
+        return Err("Frontmatter is missing".into());
+    };
+
+    if frontmatter.get("title").is_none() || frontmatter.get("date").is_none() {
+        return Err("Missing title or date in frontmatter".into());
+    }
+    if !frontmatter["title"].is_string() {
+        return Err("Title must be a string".into());
+    }
+    normalize_date(&mut frontmatter["date"])?;
+    if frontmatter.get("updated").is_some_and(|updated| !updated.is_null()) {
+        normalize_date(&mut frontmatter["updated"])?;
+    }
+    // serde's own message wouldn't name the field.
+    for field in STRING_FIELDS {
+        if frontmatter.get(field).is_some_and(|value| !value.is_null() && !value.is_string()) {
+            return Err(format!("'{}' must be a string", field).into());
+        }
+    }
+    for field in INTEGER_FIELDS {
+        if frontmatter.get(field).is_some_and(|value| !value.is_null() && !value.is_i64()) {
+            return Err(format!("'{}' must be an integer", field).into());
+        }
+    }
+    let mut typed: Frontmatter = serde_yaml::from_value(frontmatter.clone())?;
+    typed.raw = frontmatter;
+    Ok((typed, md_content))
+}
+
+/// The trimmed text before `end` and what follows `end`.
+fn fenced<'a>(rest: &'a str, end: &str) -> Result<(&'a str, &'a str), Box<dyn Error>> {
+    let frontmatter_end = rest.find(end).ok_or("Frontmatter end delimiter not found")?;
+    Ok((rest[..frontmatter_end].trim(), &rest[frontmatter_end + end.len()..]))
+}
+
+/// `block`, a slice of `content`, preceded by as many newlines as there are
+/// lines before it, so parser error locations refer to lines of the whole
+/// file.
+fn padded(content: &str, block: &str) -> String {
+    let offset = block.as_ptr() as usize - content.as_ptr() as usize;
+    "\n".repeat(content[..offset].matches('\n').count()) + block
+}
+
+fn toml_to_yaml(value: toml::Value) -> YamlValue {
+    match value {
+        toml::Value::String(s) => YamlValue::String(s),
+        toml::Value::Integer(i) => i.into(),
+        toml::Value::Float(f) => f.into(),
+        toml::Value::Boolean(b) => b.into(),
+        // Dates become strings, as unquoted YAML dates do.
+        toml::Value::Datetime(datetime) => YamlValue::String(datetime.to_string()),
+        toml::Value::Array(items) => YamlValue::Sequence(items.into_iter().map(toml_to_yaml).collect()),
+        toml::Value::Table(table) => YamlValue::Mapping(
+            table
+                .into_iter()
+                .map(|(key, value)| (YamlValue::String(key), toml_to_yaml(value)))
+                .collect(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn yaml_frontmatter() {
+        let (frontmatter, body) =
+            extract_frontmatter("---\ntitle: Hello\ndate: 2024-01-09\ntags: [a, b]\n---\nBody\n").unwrap();
+        assert_eq!(frontmatter.title, "Hello");
+        assert_eq!(frontmatter.date, "2024-01-09");
+        assert_eq!(frontmatter.tags, ["a", "b"]);
+        assert_eq!(body, "\nBody\n");
+    }
+
+    #[test]
+    fn toml_frontmatter_with_bare_date() {
+        let (frontmatter, body) =
+            extract_frontmatter("+++\ntitle = \"Hello\"\ndate = 2024-01-09\nweight = 2\n+++\nBody\n").unwrap();
+        assert_eq!(frontmatter.title, "Hello");
+        assert_eq!(frontmatter.date, "2024-01-09");
+        assert_eq!(frontmatter.weight, Some(2));
+        assert_eq!(body, "\nBody\n");
+    }
+
+    #[test]
+    fn json_frontmatter_ends_after_the_object() {
+        let (frontmatter, body) =
+            extract_frontmatter("{\"title\": \"Hello\", \"date\": \"2024-01-09\"}\n# Heading {#id}\n").unwrap();
+        assert_eq!(frontmatter.title, "Hello");
+        assert_eq!(frontmatter.date, "2024-01-09");
+        assert_eq!(body, "\n# Heading {#id}\n");
+    }
+
+    #[test]
+    fn title_and_date_are_required_in_every_format() {
+        for content in [
+            "---\ntitle: Hello\n---\n",
+            "+++\ndate = 2024-01-09\n+++\n",
+            "{\"title\": \"Hello\"}",
+        ] {
+            let error = extract_frontmatter(content).unwrap_err().to_string();
+            assert!(error.contains("Missing title or date"), "{}", error);
+        }
+    }
+
+    #[test]
+    fn updated_is_kept_apart_from_date() {
+        let (frontmatter, _) =
+            extract_frontmatter("---\ntitle: Hello\ndate: 2024-01-09\nupdated: 2024-03-01\n---\n").unwrap();
+        assert_eq!(frontmatter.date, "2024-01-09");
+        assert_eq!(frontmatter.updated(), "2024-03-01");
+    }
+
+    #[test]
+    fn updated_falls_back_to_date() {
+        let (frontmatter, _) = extract_frontmatter("---\ntitle: Hello\ndate: 2024-01-09\n---\n").unwrap();
+        assert_eq!(frontmatter.updated, None);
+        assert_eq!(frontmatter.updated(), "2024-01-09");
+    }
+}
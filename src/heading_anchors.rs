@@ -0,0 +1,48 @@
+use crate::logger::debug;
+use crate::file_ops::{static_url, write_hashed_file};
+use colored::Colorize;
+use css_minify::optimizations::{Level as CssLevel, Minifier as CssMinifier};
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::Path;
+
+/// Writes the hashed stylesheet for `[content] heading_anchors` and returns
+/// its URL keyed by unhashed file name.
+pub fn setup_heading_anchors(dist_static: &Path) -> Result<HashMap<String, String>, Box<dyn Error>> {
+    let anchors_css = r#"
+.heading-anchor {
+    margin-left: 0.25em;
+    color: var(--link-color);
+    text-decoration: none;
+    opacity: 0;
+    transition: opacity 0.15s;
+}
+
+h1:hover > .heading-anchor,
+h2:hover > .heading-anchor,
+h3:hover > .heading-anchor,
+h4:hover > .heading-anchor,
+h5:hover > .heading-anchor,
+h6:hover > .heading-anchor,
+.heading-anchor:focus {
+    opacity: 1;
+}
+"#;
+
+    let minified_css = CssMinifier::default().minify(anchors_css, CssLevel::Three)?;
+    let css_path = write_hashed_file(dist_static, "heading_anchors.css", minified_css.as_bytes())?;
+
+    debug!("{}", "Generated and minified heading_anchors.css".green());
+    Ok(HashMap::from([(
+        "heading_anchors.css".to_string(),
+        static_url(dist_static, &css_path),
+    )]))
+}
+
+/// The link appended to a heading with id `slug`.
+pub fn heading_anchor(slug: &str) -> String {
+    format!(
+        " <a class=\"heading-anchor\" href=\"#{}\" aria-label=\"Link to this section\">#</a>",
+        slug
+    )
+}
@@ -0,0 +1,62 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Another language's version of a page.
+#[derive(Debug, Serialize, Clone)]
+pub struct Translation {
+    pub lang: String,
+    /// Absolute, as `hreflang` links need.
+    pub url: String,
+}
+
+/// Pages of every language, keyed by their path below their language's root.
+#[derive(Default)]
+pub struct Translations {
+    pages: HashMap<String, Vec<Translation>>,
+}
+
+/// The language of a source file under `content/` and its path relative to
+/// that language's root: `content/ja/notes/a.md` is `("ja", "notes/a.md")`,
+/// and with `languages = ["en", "ja"]`, `content/notes/a.md` is
+/// `("en", "notes/a.md")`. `None` when `languages` is empty.
+pub fn page_language<'a>(path: &Path, languages: &'a [String]) -> Option<(&'a str, String)> {
+    let default = languages.first()?;
+    let relative = path.strip_prefix("content").unwrap_or(path);
+    let first = relative.components().next()?.as_os_str().to_string_lossy();
+    let (lang, rest) = match languages.iter().find(|lang| **lang == first) {
+        Some(lang) => (lang, relative.strip_prefix(lang.as_str()).unwrap_or(relative)),
+        None => (default, relative),
+    };
+    Some((lang.as_str(), rest.to_string_lossy().replace('\\', "/")))
+}
+
+impl Translations {
+    /// Records the page at `path`, published at `url`.
+    pub fn add(&mut self, path: &Path, url: String, languages: &[String]) {
+        if let Some((lang, key)) = page_language(path, languages) {
+            self.pages.entry(key).or_default().push(Translation {
+                lang: lang.to_string(),
+                url,
+            });
+        }
+    }
+
+    /// The other languages' versions of the page at `path`, in `languages`
+    /// order.
+    pub fn of(&self, path: &Path, languages: &[String]) -> Vec<Translation> {
+        let Some((lang, key)) = page_language(path, languages) else {
+            return Vec::new();
+        };
+        let mut translations: Vec<Translation> = self
+            .pages
+            .get(&key)
+            .into_iter()
+            .flatten()
+            .filter(|translation| translation.lang != lang)
+            .cloned()
+            .collect();
+        translations.sort_by_key(|translation| languages.iter().position(|code| *code == translation.lang));
+        translations
+    }
+}
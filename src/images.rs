@@ -1,28 +1,53 @@
-use crate::config::Config;
+use crate::logger::debug;
+use crate::config::{Config, LazyMode, PlaceholderConfig};
 use crate::file_ops::create_directory_safely;
+use crate::incremental::is_up_to_date;
 use image::{
     self, ImageEncoder, codecs::jpeg::JpegEncoder, codecs::png::PngEncoder,
     codecs::webp::WebPEncoder, imageops,
 };
 use crate::paths::STATIC_FILE_MAP;
+use crate::static_files::minify_svg_or_warn;
 use std::error::Error;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use walkdir::DirEntry;
 use colored::Colorize;
 
+/// Points JPEG and PNG references at the WebP files written when
+/// `compress_to_webp` is enabled.
+pub fn use_webp_urls(html: &str) -> String {
+    html.replace(".jpg", ".webp")
+        .replace(".jpeg", ".webp")
+        .replace(".png", ".webp")
+}
+
+/// Whether `path` is an image by extension, as opposed to the other files
+/// (PDFs, downloads) copied from `content/` alongside them.
+pub fn is_image(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| {
+            matches!(
+                ext.to_lowercase().as_str(),
+                "jpg" | "jpeg" | "png" | "webp" | "gif" | "svg" | "avif"
+            )
+        })
+}
+
 pub fn create_placeholder_image(
     img_path: &Path,
     output_path: &Path,
     use_webp: bool,
+    placeholder: &PlaceholderConfig,
 ) -> Result<(), Box<dyn Error>> {
     let img = image::open(img_path)?;
 
-    let width = 20;
-    let height = (img.height() as f32 * (width as f32 / img.width() as f32)) as u32;
+    let width = placeholder.width;
+    let height = ((img.height() as f32 * (width as f32 / img.width() as f32)) as u32).max(1);
 
     let tiny = img.resize(width, height, imageops::FilterType::Triangle);
-    let blurred = tiny.blur(3.0);
+    let blurred = tiny.blur(placeholder.blur);
 
     if let Some(parent) = output_path.parent() {
         create_directory_safely(parent)?;
@@ -41,7 +66,7 @@ pub fn create_placeholder_image(
     } else if output_path.extension().and_then(|e| e.to_str()) == Some("jpg")
         || output_path.extension().and_then(|e| e.to_str()) == Some("jpeg")
     {
-        let mut encoder = JpegEncoder::new_with_quality(&mut buffer, 30);
+        let mut encoder = JpegEncoder::new_with_quality(&mut buffer, placeholder.quality.clamp(1, 100));
         encoder.encode_image(&blurred)?;
     } else {
         let encoder = PngEncoder::new_with_quality(
@@ -61,21 +86,72 @@ pub fn create_placeholder_image(
     Ok(())
 }
 
+/// Copies a content asset as-is, for images that couldn't be decoded. With
+/// `compress_to_webp` a JPEG or PNG still goes to its `.webp` path, since
+/// `use_webp_urls` points every page there; browsers sniff the real format.
+pub fn copy_content_file(entry: &DirEntry, dist_static: &Path, config: &Config) -> Result<PathBuf, Box<dyn Error>> {
+    let relative_path = entry.path().strip_prefix("content")?;
+    let mut output_path = dist_static.join(crate::paths::static_asset_name(&relative_path.to_string_lossy()));
+    let extension = entry.path().extension().and_then(|s| s.to_str().map(|s| s.to_lowercase()));
+    if config.images.compress_to_webp && matches!(extension.as_deref(), Some("jpg" | "jpeg" | "png")) {
+        output_path.set_extension("webp");
+    }
+    create_directory_safely(output_path.parent().unwrap())?;
+    fs::copy(entry.path(), &output_path)?;
+    Ok(output_path)
+}
+
+/// Processes one content asset and returns every file written for it (the
+/// output itself plus its placeholder, if any). With `skip_unchanged`, an
+/// asset whose output is newer than its source is left alone.
+///
+/// Only JPEG and PNG are re-encoded. GIFs are always copied byte for byte,
+/// since re-encoding would flatten an animation to its first frame, and SVG
+/// is not raster data at all: it is at most minified (`[build] minify_svg`).
+/// Anything else is copied as well.
 pub fn process_content_images(
     entry: &DirEntry,
     dist_static: &Path,
     lazy_dir: &Path,
     config: &Config,
-) -> Result<(), Box<dyn Error>> {
+    skip_unchanged: bool,
+) -> Result<Vec<PathBuf>, Box<dyn Error>> {
     let relative_path = entry.path().strip_prefix("content")?;
-    let sanitized_name = crate::utils::sanitize_filename(&relative_path.to_string_lossy());
+    let sanitized_name = crate::paths::static_asset_name(&relative_path.to_string_lossy());
     let mut output_path = dist_static.join(&sanitized_name);
     create_directory_safely(output_path.parent().unwrap())?;
 
     let mut map = STATIC_FILE_MAP.lock().unwrap();
     map.insert(sanitized_name.clone(), entry.path().to_path_buf());
 
-    match entry.path().extension().and_then(|s| s.to_str().map(|s| s.to_lowercase())) {
+    let extension = entry.path().extension().and_then(|s| s.to_str().map(|s| s.to_lowercase()));
+    let has_placeholder = match extension.as_deref() {
+        Some("jpg") | Some("jpeg") | Some("png") => {
+            if config.images.compress_to_webp {
+                output_path.set_extension("webp");
+            }
+            config.images.lazy_mode == LazyMode::Placeholder
+        }
+        _ => false,
+    };
+    // The placeholder mirrors the output's path (extension included) under
+    // lazy/, which is where `add_lazy_loading` looks for it.
+    let placeholder_path = has_placeholder.then(|| {
+        let relative_output = output_path.strip_prefix(dist_static).unwrap_or(&output_path);
+        lazy_dir.join(relative_output)
+    });
+    if let Some(parent) = placeholder_path.as_ref().and_then(|p| p.parent()) {
+        create_directory_safely(parent)?;
+    }
+    let outputs: Vec<PathBuf> = std::iter::once(output_path.clone())
+        .chain(placeholder_path.clone())
+        .collect();
+
+    if skip_unchanged && outputs.iter().all(|output| is_up_to_date(entry.path(), output)) {
+        return Ok(outputs);
+    }
+
+    match extension {
         Some(ext) if (ext == "jpg" || ext == "jpeg" || ext == "png") && config.images.compress_to_webp => {
             let img = image::open(entry.path())?;
             let rgba_img = img.to_rgba8();
@@ -88,14 +164,12 @@ pub fn process_content_images(
                 image::ExtendedColorType::Rgba8,
             )?;
 
-            output_path.set_extension("webp");
             fs::write(&output_path, &buffer)?;
+            if let Some(placeholder_path) = &placeholder_path {
+                create_placeholder_image(entry.path(), placeholder_path, true, &config.images.placeholder)?;
+            }
 
-            let file_stem = output_path.file_stem().unwrap_or_default().to_string_lossy();
-            let placeholder_path = lazy_dir.join(format!("{}.webp", file_stem));
-            create_placeholder_image(entry.path(), &placeholder_path, true)?;
-
-            println!(
+            debug!(
                 "{} {} -> {} (WebP) with placeholder",
                 "Converting".green(),
                 entry.path().display().to_string().replace('\\', "/").yellow(),
@@ -110,12 +184,11 @@ pub fn process_content_images(
             encoder.encode_image(&img)?;
 
             fs::write(&output_path, &buffer)?;
+            if let Some(placeholder_path) = &placeholder_path {
+                create_placeholder_image(entry.path(), placeholder_path, false, &config.images.placeholder)?;
+            }
 
-            let file_stem = output_path.file_stem().unwrap_or_default().to_string_lossy();
-            let placeholder_path = lazy_dir.join(format!("{}.jpg", file_stem));
-            create_placeholder_image(entry.path(), &placeholder_path, false)?;
-
-            println!(
+            debug!(
                 "{} {} -> {} (quality: {}) with placeholder",
                 "Compressing".green(),
                 entry.path().display().to_string().replace('\\', "/").yellow(),
@@ -146,12 +219,11 @@ pub fn process_content_images(
             )?;
 
             fs::write(&output_path, &buffer)?;
+            if let Some(placeholder_path) = &placeholder_path {
+                create_placeholder_image(entry.path(), placeholder_path, false, &config.images.placeholder)?;
+            }
 
-            let file_stem = output_path.file_stem().unwrap_or_default().to_string_lossy();
-            let placeholder_path = lazy_dir.join(format!("{}.png", file_stem));
-            create_placeholder_image(entry.path(), &placeholder_path, false)?;
-
-            println!(
+            debug!(
                 "{} {} -> {} (quality: {}) with placeholder",
                 "Compressing".green(),
                 entry.path().display().to_string().yellow().replace('\\', "/").yellow(),
@@ -159,9 +231,31 @@ pub fn process_content_images(
                 quality.to_string().cyan()
             );
         }
+        Some(ext) if ext == "svg" && config.build.minify_svg => {
+            let content = fs::read(entry.path())?;
+            match minify_svg_or_warn(entry.path(), &content) {
+                Some(minified) => fs::write(&output_path, minified)?,
+                None => fs::write(&output_path, &content)?,
+            }
+            debug!(
+                "{} {} -> {}",
+                "Copying and minifying".green(),
+                entry.path().display().to_string().replace('\\', "/").yellow(),
+                output_path.display().to_string().replace('\\', "/").yellow()
+            );
+        }
+        Some(ext) if ext == "gif" || ext == "svg" => {
+            fs::copy(entry.path(), &output_path)?;
+            debug!(
+                "{} {} -> {} (not re-encoded)",
+                "Copying".green(),
+                entry.path().display().to_string().replace('\\', "/").yellow(),
+                output_path.display().to_string().replace('\\', "/").yellow()
+            );
+        }
         _ => {
             fs::copy(entry.path(), &output_path)?;
-            println!(
+            debug!(
                 "{} {} -> {}",
                 "Copying".green(),
                 entry.path().display().to_string().yellow().replace('\\', "/").yellow(),
@@ -169,5 +263,5 @@ pub fn process_content_images(
             );
         }
     }
-    Ok(())
+    Ok(outputs)
 }
\ No newline at end of file
@@ -0,0 +1,86 @@
+use crate::file_ops::{CACHE_DIR, create_directory_safely};
+use std::collections::HashSet;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const STATE_FILE: &str = "build-state";
+
+/// What the previous build produced, used by `--incremental` to decide what
+/// can be skipped and which outputs have become orphans.
+///
+/// `fingerprint` covers everything that feeds into every page at once: the
+/// config, templates, the list of content files and all page metadata and
+/// backlinks. If it changes, every page is re-rendered; otherwise only pages
+/// whose source is newer than their output are.
+pub struct BuildState {
+    pub fingerprint: u64,
+    pub outputs: Vec<PathBuf>,
+}
+
+impl BuildState {
+    fn path() -> PathBuf {
+        Path::new(CACHE_DIR).join(STATE_FILE)
+    }
+
+    pub fn load() -> Option<Self> {
+        let content = fs::read_to_string(Self::path()).ok()?;
+        let mut lines = content.lines();
+        let fingerprint = lines.next()?.parse().ok()?;
+        let outputs = lines.map(PathBuf::from).collect();
+        Some(BuildState {
+            fingerprint,
+            outputs,
+        })
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn Error>> {
+        create_directory_safely(Path::new(CACHE_DIR))?;
+        let mut content = format!("{}\n", self.fingerprint);
+        for output in &self.outputs {
+            content.push_str(&output.to_string_lossy());
+            content.push('\n');
+        }
+        fs::write(Self::path(), content)?;
+        Ok(())
+    }
+}
+
+/// True when `output` exists and was written after `source` was last modified.
+pub fn is_up_to_date(source: &Path, output: &Path) -> bool {
+    let modified = |path: &Path| fs::metadata(path).and_then(|m| m.modified()).ok();
+    match (modified(source), modified(output)) {
+        (Some(source_time), Some(output_time)) => output_time >= source_time,
+        _ => false,
+    }
+}
+
+/// Removes outputs the previous build produced that this build no longer
+/// does, along with any directories left empty inside `dist`.
+pub fn prune_orphans(
+    previous: &BuildState,
+    current_outputs: &HashSet<PathBuf>,
+    dist: &Path,
+) -> Result<usize, Box<dyn Error>> {
+    let mut removed = 0;
+    for output in &previous.outputs {
+        if current_outputs.contains(output) || !output.exists() {
+            continue;
+        }
+        fs::remove_file(output)?;
+        removed += 1;
+
+        let mut dir = output.parent();
+        while let Some(current) = dir {
+            if current == dist || !current.starts_with(dist) {
+                break;
+            }
+            if fs::read_dir(current)?.next().is_some() {
+                break;
+            }
+            fs::remove_dir(current)?;
+            dir = current.parent();
+        }
+    }
+    Ok(removed)
+}
@@ -0,0 +1,48 @@
+use crate::config::Author;
+use crate::dates::parse_date;
+use serde_json::{Map, Value, json};
+
+/// What a content page knows about itself, for its schema.org data. Empty
+/// strings mean "not set".
+pub struct ArticleData<'a> {
+    pub title: &'a str,
+    pub url: &'a str,
+    pub date: &'a str,
+    pub updated: &'a str,
+    pub author: Option<&'a Author>,
+    pub description: &'a str,
+    pub image: &'a str,
+}
+
+/// A schema.org `BlogPosting` as JSON, ready for a
+/// `<script type="application/ld+json">`. Fields the page doesn't set (or a
+/// date that doesn't parse) are left out rather than emitted empty.
+pub fn article_json_ld(article: &ArticleData) -> String {
+    let mut data = Map::new();
+    data.insert("@context".to_string(), json!("https://schema.org"));
+    data.insert("@type".to_string(), json!("BlogPosting"));
+    data.insert("headline".to_string(), json!(article.title));
+    data.insert("url".to_string(), json!(article.url));
+    data.insert("mainEntityOfPage".to_string(), json!(article.url));
+    if let Ok(date) = parse_date(article.date) {
+        data.insert("datePublished".to_string(), json!(date.to_rfc3339()));
+    }
+    if let Ok(updated) = parse_date(article.updated) {
+        data.insert("dateModified".to_string(), json!(updated.to_rfc3339()));
+    }
+    if let Some(author) = article.author {
+        let mut person = json!({ "@type": "Person", "name": author.name });
+        if !author.url.is_empty() {
+            person["url"] = json!(author.url);
+        }
+        data.insert("author".to_string(), person);
+    }
+    if !article.description.is_empty() {
+        data.insert("description".to_string(), json!(article.description));
+    }
+    if !article.image.is_empty() {
+        data.insert("image".to_string(), json!(article.image));
+    }
+    // `</script>` inside a string would end the element early.
+    Value::Object(data).to_string().replace("</", "<\\/")
+}
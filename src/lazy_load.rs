@@ -1,12 +1,18 @@
-use crate::file_ops::safely_write_file;
+use crate::logger::debug;
+use crate::file_ops::{static_url, write_hashed_file};
+use std::collections::HashMap;
 use css_minify::optimizations::{Level as CssLevel, Minifier as CssMinifier};
 use minify_js::{Session, TopLevelMode, minify as js_minify};
 use std::error::Error;
 use std::path::Path;
-use regex;
+use crate::config::LazyMode;
+use lazy_static::lazy_static;
+use regex::Regex;
 use colored::Colorize;
 
-pub fn setup_lazy_loading(dist_static: &Path) -> Result<(), Box<dyn Error>> {
+/// Writes the hashed lazy-loading script and stylesheet and returns their
+/// URLs keyed by unhashed file name.
+pub fn setup_lazy_loading(dist_static: &Path) -> Result<HashMap<String, String>, Box<dyn Error>> {
     let lazy_loading_js = r#"
 document.addEventListener('DOMContentLoaded', () => {
     const lazyImages = document.querySelectorAll('img[data-src]');
@@ -81,44 +87,99 @@ document.addEventListener('DOMContentLoaded', () => {
         lazy_loading_js.as_bytes(),
         &mut minified_js,
     ).expect("Failed to minify JS");
-    safely_write_file(
-        &dist_static.join("lazyload.js"),
-        std::str::from_utf8(&minified_js)?,
-    )?;
+    let js_path = write_hashed_file(dist_static, "lazyload.js", &minified_js)?;
     let minified_css = CssMinifier::default()
         .minify(&lazy_loading_css, CssLevel::Three)?;
-    safely_write_file(&dist_static.join("lazyload.css"), &minified_css)?;
+    let css_path = write_hashed_file(dist_static, "lazyload.css", minified_css.as_bytes())?;
 
-    println!("{}", "Generated and minified lazyload.js and lazyload.css".green());
-    Ok(())
+    debug!("{}", "Generated and minified lazyload.js and lazyload.css".green());
+    Ok(HashMap::from([
+        ("lazyload.js".to_string(), static_url(dist_static, &js_path)),
+        ("lazyload.css".to_string(), static_url(dist_static, &css_path)),
+    ]))
 }
 
-pub fn add_lazy_loading(html: &str, compress_to_webp: bool) -> String {
-        let mut modified_html = html.to_string();
-        let re = regex::Regex::new(r#"<img\s+([^>]*)src="([^"]+)"([^>]*)>"#).unwrap();
-
-        modified_html = re.replace_all(&modified_html, |caps: &regex::Captures| {
-            let attrs_before = &caps[1];
-            let src = &caps[2];
-            let attrs_after = &caps[3];
-            
-            let src_path = Path::new(src);
-            let file_stem = src_path.file_stem().unwrap_or_default().to_string_lossy();
-            let orig_ext = src_path.extension().unwrap_or_default().to_string_lossy();
-            
-            let placeholder_path = if compress_to_webp {
-                format!("/static/lazy/{}.webp", file_stem)
-            } else {
-                format!("/static/lazy/{}.{}", file_stem, orig_ext)
+lazy_static! {
+    static ref IMG_TAG: Regex = Regex::new(r#"<img\s+([^>]*)src="([^"]+)"([^>]*)>"#).unwrap();
+    static ref LOADING_ATTR: Regex = Regex::new(r#"\sloading="#).unwrap();
+}
+
+/// Rewrites `<img>` tags in rendered content for `mode`: placeholder
+/// containers with `data-src`, native `loading="lazy"`, or nothing at all.
+pub fn add_lazy_loading(html: &str, mode: &LazyMode) -> String {
+    match mode {
+        LazyMode::Placeholder => add_placeholders(html),
+        LazyMode::Native => add_native_lazy_loading(html),
+        LazyMode::Off => html.to_string(),
+    }
+}
+
+fn add_native_lazy_loading(html: &str) -> String {
+    IMG_TAG
+        .replace_all(html, |caps: &regex::Captures| {
+            let tag = &caps[0];
+            if LOADING_ATTR.is_match(tag) {
+                return tag.to_string();
+            }
+            let (start, end) = match tag.strip_suffix("/>") {
+                Some(start) => (start.trim_end(), " />"),
+                None => (tag.strip_suffix('>').unwrap_or(tag), ">"),
             };
-            
-            format!(
-                r#"<div class="lazy-image-container">
-                    <img {}src="{}" data-src="{}" loading="lazy" {}><img class="placeholder" src="{}" alt="loading...">
-                </div>"#,
-                attrs_before, placeholder_path, src, attrs_after, placeholder_path
-            )
-        }).to_string();
-
-        modified_html
-}
\ No newline at end of file
+            format!(r#"{} loading="lazy" decoding="async"{}"#, start, end)
+        })
+        .to_string()
+}
+
+/// Where `process_content_images` writes the placeholder for an image
+/// served at `src`: the same path under `/static/lazy/`. Only local JPEG
+/// and PNG sources get one; `compress_to_webp` renames both to `.webp` after
+/// this runs.
+fn placeholder_url(src: &str) -> Option<String> {
+    let relative = src.strip_prefix("/static/")?;
+    let extension = Path::new(relative).extension()?.to_str()?.to_lowercase();
+    matches!(extension.as_str(), "jpg" | "jpeg" | "png")
+        .then(|| format!("/static/lazy/{}", relative))
+}
+
+fn add_placeholders(html: &str) -> String {
+    IMG_TAG.replace_all(html, |caps: &regex::Captures| {
+        let src = &caps[2];
+        let Some(placeholder_path) = placeholder_url(src) else {
+            return caps[0].to_string();
+        };
+
+        format!(
+            r#"<div class="lazy-image-container">
+                <img {}src="{}" data-src="{}" loading="lazy" {}><img class="placeholder" src="{}" alt="loading...">
+            </div>"#,
+            &caps[1], placeholder_path, src, &caps[3], placeholder_path
+        )
+    }).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::paths::static_asset_name;
+
+    #[test]
+    fn same_named_images_get_distinct_placeholders() {
+        let a = format!("/static/{}", static_asset_name("a/logo.png"));
+        let b = format!("/static/{}", static_asset_name("b/logo.png"));
+        let html = format!(r#"<img src="{}" alt="a"><img src="{}" alt="b">"#, a, b);
+        let lazy = add_placeholders(&html);
+        assert!(lazy.contains(&format!(r#"src="/static/lazy/{}""#, static_asset_name("a/logo.png"))));
+        assert!(lazy.contains(&format!(r#"src="/static/lazy/{}""#, static_asset_name("b/logo.png"))));
+        assert_ne!(placeholder_url(&a), placeholder_url(&b));
+    }
+
+    #[test]
+    fn only_jpeg_and_png_sources_get_placeholders() {
+        assert_eq!(placeholder_url("/static/photo.JPG").as_deref(), Some("/static/lazy/photo.JPG"));
+        assert_eq!(placeholder_url("/static/photo.webp"), None);
+        assert_eq!(placeholder_url("/static/anim.gif"), None);
+        assert_eq!(placeholder_url("https://example.com/a.png"), None);
+        let html = r#"<img src="/static/photo.webp" alt="">"#;
+        assert_eq!(add_placeholders(html), html);
+    }
+}
@@ -0,0 +1,46 @@
+//! Sekiei's site generator as a library: [`build_site`] renders `content/`
+//! with `templates/` and `static/` from the current directory into `dist/`,
+//! the same as `sekiei build`.
+
+pub mod build;
+mod callouts;
+pub mod check;
+pub mod clean;
+mod code_blocks;
+pub mod config;
+mod dates;
+mod external_links;
+mod file_ops;
+mod file_tree;
+mod filters;
+mod frontmatter;
+mod heading_anchors;
+mod i18n;
+mod images;
+mod incremental;
+mod json_ld;
+mod lazy_load;
+mod listing;
+pub mod logger;
+mod markdown;
+mod og;
+mod pages;
+mod paths;
+mod precompress;
+mod redirects;
+mod robots;
+mod rss;
+mod sanitize;
+pub mod serve;
+mod site;
+mod static_files;
+mod svg;
+mod tables;
+mod theme;
+pub mod theme_cli;
+mod utils;
+
+pub use build::{BuildOptions, BuildReport, PageInfo, build, build_site};
+pub use config::{Config, GeneralConfig, ThemeConfig, ThemeType};
+pub use frontmatter::Frontmatter;
+pub use precompress::Savings;
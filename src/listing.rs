@@ -1,20 +1,38 @@
 use serde::Serialize;
 use std::{error::Error, fs, path::Path};
 
-use crate::markdown::extract_frontmatter;
+use crate::{
+    config::Config,
+    dates::{DISPLAY_FORMAT, parse_date},
+    images::use_webp_urls,
+    markdown::excerpt_html,
+    pages::Pages,
+    paths::route_for,
+};
+use chrono::{DateTime, FixedOffset, SecondsFormat};
 
 #[derive(Serialize)]
 pub struct ListingItem {
     pub name: String,
     pub url: String,
+    /// RFC 3339, for `<time datetime>`.
     pub date: String,
+    /// `date` for readers, e.g. `9 Jan 1984`.
+    pub date_display: String,
     pub description: Option<String>,
+    /// Summary as HTML (see `excerpt_html`); empty for other files.
+    pub excerpt: String,
+    pub weight: Option<i64>,
 }
 
-pub fn create_listing(dir: &Path) -> Result<Vec<ListingItem>, Box<dyn Error>> {
+/// The files directly in `dir`, weighted pages first (lowest weight first)
+/// and the rest by file name. Pages the build left out of `pages` (drafts,
+/// and pages with invalid frontmatter) are left out here too.
+pub fn create_listing(dir: &Path, config: &Config, pages: &Pages) -> Result<Vec<ListingItem>, Box<dyn Error>> {
     let mut items = Vec::new();
     for entry in walkdir::WalkDir::new(dir)
         .max_depth(1)
+        .sort_by_file_name()
         .into_iter()
         .filter_map(|e| e.ok())
     {
@@ -30,42 +48,91 @@ pub fn create_listing(dir: &Path) -> Result<Vec<ListingItem>, Box<dyn Error>> {
             .to_string();
 
         if entry.file_type().is_file() && name.ends_with(".md") {
-            let rel_path = path
-                .with_extension("")
-                .strip_prefix("content")?
-                .to_string_lossy()
-                .to_string();
-            let url = format!("/{}", rel_path);
-            let content = fs::read_to_string(path)?;
-            let (frontmatter, _) = extract_frontmatter(&content)?;
+            let Some(page) = pages.get(path) else {
+                continue;
+            };
+            let url = route_for(path);
+            let frontmatter = &page.frontmatter;
 
+            // An unparseable date is shown as written rather than failing
+            // the listing; `check` reports it.
+            let (date, date_display) = match parse_date(&frontmatter.date) {
+                Ok(date) => listing_dates(date),
+                Err(_) => (frontmatter.date.clone(), frontmatter.date.clone()),
+            };
+            let (html, _) = page.rendered(config);
+            let mut excerpt = excerpt_html(&page.markdown, html, frontmatter.description.as_deref(), path, config);
+            if config.images.compress_to_webp {
+                excerpt = use_webp_urls(&excerpt);
+            }
             items.push(ListingItem {
-                name: frontmatter["title"]
-                    .as_str()
-                    .unwrap_or_default()
-                    .to_string(),
+                name: frontmatter.title.clone(),
                 url,
-                date: frontmatter["date"].as_str().unwrap_or_default().to_string(),
-                description: frontmatter["description"].as_str().map(|s| s.to_string()),
+                date,
+                date_display,
+                description: frontmatter.description.clone(),
+                excerpt,
+                weight: frontmatter.weight,
             });
         } else if entry.file_type().is_file() {
             let rel_path = path.strip_prefix("content")?.to_string_lossy().to_string();
-            let sanitized_name = crate::utils::sanitize_filename(&rel_path);
+            let sanitized_name = crate::paths::static_asset_name(&rel_path);
             let url = format!("/static/{}", sanitized_name);
-            let metadata = fs::metadata(path)?;
-            let modified_time = metadata.modified()?;
-            let date = modified_time
-                .duration_since(std::time::UNIX_EPOCH)?
-                .as_secs()
-                .to_string();
+            let modified: DateTime<chrono::Utc> = fs::metadata(path)?.modified()?.into();
+            let (date, date_display) = listing_dates(modified.fixed_offset());
 
             items.push(ListingItem {
                 name: name.clone(),
                 url,
                 date,
+                date_display,
                 description: None,
+                excerpt: String::new(),
+                weight: None,
             });
         }
     }
+    sort_items(&mut items);
     Ok(items)
 }
+
+/// Weighted items first, lowest weight first. Stable, so equal weights and
+/// unweighted items keep their order.
+fn sort_items(items: &mut [ListingItem]) {
+    items.sort_by_key(|item| (item.weight.is_none(), item.weight));
+}
+
+fn listing_dates(date: DateTime<FixedOffset>) -> (String, String) {
+    (date.to_rfc3339_opts(SecondsFormat::Secs, false), date.format(DISPLAY_FORMAT).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(name: &str, weight: Option<i64>) -> ListingItem {
+        ListingItem {
+            name: name.to_string(),
+            url: format!("/{}", name),
+            date: String::new(),
+            date_display: String::new(),
+            description: None,
+            excerpt: String::new(),
+            weight,
+        }
+    }
+
+    #[test]
+    fn weighted_items_come_first_and_the_rest_keep_file_order() {
+        let mut items = vec![
+            item("a", None),
+            item("b", Some(2)),
+            item("c", None),
+            item("d", Some(1)),
+            item("e", Some(2)),
+        ];
+        sort_items(&mut items);
+        let names: Vec<&str> = items.iter().map(|item| item.name.as_str()).collect();
+        assert_eq!(names, ["d", "b", "e", "a", "c"]);
+    }
+}
@@ -0,0 +1,95 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::fmt;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+
+/// Verbosity of a log line; a line is shown when its level is at or below
+/// the configured maximum.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Error = 0,
+    Warn = 1,
+    Info = 2,
+    Debug = 3,
+}
+
+impl Level {
+    fn name(self) -> &'static str {
+        match self {
+            Level::Error => "error",
+            Level::Warn => "warn",
+            Level::Info => "info",
+            Level::Debug => "debug",
+        }
+    }
+}
+
+static MAX_LEVEL: AtomicU8 = AtomicU8::new(Level::Info as u8);
+static JSON: AtomicBool = AtomicBool::new(false);
+
+lazy_static! {
+    /// Warnings logged since the last `take_warnings`, shown or not.
+    static ref WARNINGS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+    static ref ANSI_ESCAPE: Regex = Regex::new(r"\x1b\[[0-9;]*m").unwrap();
+}
+
+/// Sets the maximum level shown and whether lines are emitted as JSON
+/// objects (`{"level": ..., "message": ...}`) instead of coloured text.
+pub fn init(max_level: Level, json: bool) {
+    MAX_LEVEL.store(max_level as u8, Ordering::Relaxed);
+    JSON.store(json, Ordering::Relaxed);
+    if json {
+        colored::control::set_override(false);
+    }
+}
+
+pub fn enabled(level: Level) -> bool {
+    level as u8 <= MAX_LEVEL.load(Ordering::Relaxed)
+}
+
+/// Returns and forgets the warnings logged so far, without colours, so a
+/// build can report them.
+pub fn take_warnings() -> Vec<String> {
+    std::mem::take(&mut *WARNINGS.lock().unwrap())
+}
+
+pub fn log(level: Level, message: fmt::Arguments) {
+    if level == Level::Warn {
+        let message = message.to_string();
+        WARNINGS.lock().unwrap().push(ANSI_ESCAPE.replace_all(&message, "").into_owned());
+    }
+    if !enabled(level) {
+        return;
+    }
+    let line = if JSON.load(Ordering::Relaxed) {
+        serde_json::json!({ "level": level.name(), "message": message.to_string() }).to_string()
+    } else {
+        message.to_string()
+    };
+    if level <= Level::Warn {
+        eprintln!("{}", line);
+    } else {
+        println!("{}", line);
+    }
+}
+
+macro_rules! error {
+    ($($arg:tt)*) => { $crate::logger::log($crate::logger::Level::Error, format_args!($($arg)*)) };
+}
+
+// Named with a suffix so the re-export below does not clash with the
+// built-in `#[warn]` attribute.
+macro_rules! warn_ {
+    ($($arg:tt)*) => { $crate::logger::log($crate::logger::Level::Warn, format_args!($($arg)*)) };
+}
+
+macro_rules! info {
+    ($($arg:tt)*) => { $crate::logger::log($crate::logger::Level::Info, format_args!($($arg)*)) };
+}
+
+macro_rules! debug {
+    ($($arg:tt)*) => { $crate::logger::log($crate::logger::Level::Debug, format_args!($($arg)*)) };
+}
+
+pub(crate) use {debug, error, info, warn_ as warn};
@@ -1,20 +1,7 @@
-mod build;
-mod config;
-mod file_ops;
-mod listing;
-mod markdown;
-mod file_tree;
-mod paths;
-mod serve;
-mod utils;
-mod images;
-mod static_files;
-mod theme;
-mod lazy_load;
-mod rss;
-
 use clap::{Parser, Subcommand};
+use sekiei::{build, check, clean, config, logger, serve, theme_cli};
 use std::error::Error;
+use std::path::PathBuf;
 
 #[derive(Parser)]
 #[clap(name = "sekiei")]
@@ -22,21 +9,124 @@ use std::error::Error;
 struct Cli {
     #[clap(subcommand)]
     command: Commands,
+
+    /// Only print warnings and errors
+    #[clap(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Also print a line for every file processed
+    #[clap(short, long, global = true)]
+    verbose: bool,
+
+    /// Print log lines as JSON objects
+    #[clap(long, global = true)]
+    json: bool,
+
+    /// Site configuration file, relative to the current directory
+    #[clap(long, global = true, value_name = "PATH", default_value = config::DEFAULT_CONFIG)]
+    config: PathBuf,
 }
 
 #[derive(Subcommand)]
 enum Commands {
-    Build,
-    Serve,
+    Build {
+        /// Only re-render pages whose sources changed since the last build
+        #[clap(long)]
+        incremental: bool,
+        /// Fail on images that can't be processed instead of copying them unchanged,
+        /// and on pages with invalid frontmatter instead of skipping them
+        #[clap(long)]
+        strict: bool,
+        /// Override `base_url` from Config.toml (e.g. for preview deployments)
+        #[clap(long, value_name = "URL")]
+        base_url: Option<String>,
+        /// Override `base_path` from Config.toml
+        #[clap(long, value_name = "PATH")]
+        base_path: Option<String>,
+        /// Also build pages marked `draft: true`
+        #[clap(long)]
+        drafts: bool,
+    },
+    Serve {
+        /// Serve over HTTPS, with a self-signed certificate unless --cert and --key are given
+        #[clap(long)]
+        tls: bool,
+        /// PEM certificate for --tls
+        #[clap(long, value_name = "PATH", requires_all = ["tls", "key"])]
+        cert: Option<PathBuf>,
+        /// PEM private key for --tls
+        #[clap(long, value_name = "PATH", requires_all = ["tls", "cert"])]
+        key: Option<PathBuf>,
+    },
+    Clean,
+    /// Validate frontmatter and internal links without writing any output
+    Check,
+    /// Browse the built-in theme presets
+    Themes {
+        #[clap(subcommand)]
+        command: ThemesCommand,
+    },
+}
+
+#[derive(Subcommand)]
+enum ThemesCommand {
+    /// List preset names with swatches of their light and dark palettes
+    List {
+        /// Print every variable of one preset instead
+        #[clap(long, value_name = "NAME")]
+        show: Option<String>,
+    },
+    /// Write presets as standalone theme-<name>.css files
+    Export {
+        /// Export only this preset
+        name: Option<String>,
+        /// Directory to write the files to
+        #[clap(long, default_value = "themes")]
+        out: PathBuf,
+    },
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
     let cli = Cli::parse();
+    let level = if cli.quiet {
+        logger::Level::Warn
+    } else if cli.verbose {
+        logger::Level::Debug
+    } else {
+        logger::Level::Info
+    };
+    logger::init(level, cli.json);
 
     match cli.command {
-        Commands::Build => build::build()?,
-        Commands::Serve => serve::serve().await?,
+        Commands::Build {
+            incremental,
+            strict,
+            base_url,
+            base_path,
+            drafts,
+        } => build::build(&build::BuildOptions {
+            config_path: cli.config,
+            incremental,
+            strict,
+            base_url,
+            base_path,
+            drafts,
+        })?,
+        Commands::Serve { tls, cert, key } => {
+            let tls = match (tls, cert, key) {
+                (false, _, _) => None,
+                (true, Some(cert), Some(key)) => Some(serve::Tls::Files { cert, key }),
+                (true, _, _) => Some(serve::Tls::SelfSigned),
+            };
+            serve::serve(cli.config, tls).await?
+        }
+        Commands::Clean => clean::clean(&cli.config)?,
+        Commands::Check => check::check(&cli.config)?,
+        Commands::Themes { command } => match command {
+            ThemesCommand::List { show } => theme_cli::list_themes(show.as_deref())?,
+            ThemesCommand::Export { name, out } => theme_cli::export_themes(&out, name.as_deref())?,
+        },
     }
 
     Ok(())
@@ -1,23 +1,35 @@
+use crate::logger::warn;
+use crate::callouts::render_callouts;
+use crate::config::Config;
+use crate::external_links::mark_external_links;
+use crate::heading_anchors::heading_anchor;
 use crate::paths::{process_paths, process_wiki_parenthetical_links, STATIC_FILE_MAP};
-use htmlescape;
+use crate::sanitize::sanitize_events;
+use crate::tables::render_tables;
+use crate::utils::slugify;
 use inkjet::{Highlighter, Language, formatter};
 use lazy_static::lazy_static;
 use pulldown_cmark::{CodeBlockKind, Event, Options, Parser, Tag, TagEnd, html};
 use regex::Regex;
 use serde::Serialize;
-use serde_yaml::Value as YamlValue;
 use std::collections::{HashMap, HashSet};
-use std::error::Error;
 use std::path::Path;
 use std::sync::Mutex;
 use infer::Infer;
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Clone, Hash)]
 pub struct Backlink {
     pub title: String,
     pub path: String,
+    /// Plain text around the link on the linking page; empty when none
+    /// could be extracted.
+    pub context: String,
 }
 
+/// Longest backlink snippet, in characters, before it is cut down to a
+/// window around the link.
+const SNIPPET_LENGTH: usize = 160;
+
 lazy_static! {
     pub static ref LANGUAGE_MAP: HashMap<&'static str, Language> = {
         let mut m = HashMap::new();
@@ -51,15 +63,212 @@ lazy_static! {
     };
     pub static ref FRONTMATTER_REGEX: Regex =
         Regex::new(r"(?s)^-{3,}\s*\n(.*?)\n-{3,}\s*\n(.*)").unwrap();
+    /// `<!-- more -->` (or `<!--more-->`) on a line of its own.
+    static ref MORE_MARKER: Regex = Regex::new(r"(?m)^[ \t]*<!--\s*more\s*-->[ \t]*\r?\n?").unwrap();
+    static ref FIRST_PARAGRAPH: Regex = Regex::new(r"(?s)<p>.*?</p>").unwrap();
+}
+
+/// The summary of a page for listings and feeds, as HTML: the markdown before
+/// a `<!-- more -->` line, else the frontmatter `description`, else the first
+/// paragraph of `html` (the whole page, rendered).
+pub fn excerpt_html(
+    markdown: &str,
+    html: &str,
+    description: Option<&str>,
+    file_path: &Path,
+    config: &Config,
+) -> String {
+    if let Some(marker) = MORE_MARKER.find(markdown) {
+        return markdown_to_html(&markdown[..marker.start()], file_path, config).0;
+    }
+    if let Some(description) = description.filter(|description| !description.trim().is_empty()) {
+        return format!("<p>{}</p>", htmlescape::encode_minimal(description.trim()));
+    }
+    FIRST_PARAGRAPH
+        .find(html)
+        .map(|paragraph| paragraph.as_str().to_string())
+        .unwrap_or_default()
+}
+
+/// Finds every link in processed markdown together with the plain text of
+/// the paragraph (or heading, list item or table cell) containing it.
+pub fn link_snippets(markdown: &str) -> Vec<(String, String)> {
+    let mut snippets = Vec::new();
+    let mut block_text = String::new();
+    // Destination and character offset in `block_text` of each pending link.
+    let mut pending: Vec<(String, usize)> = Vec::new();
+
+    let mut flush = |block_text: &mut String, pending: &mut Vec<(String, usize)>| {
+        for (dest_url, offset) in pending.drain(..) {
+            snippets.push((dest_url, snippet_around(block_text, offset)));
+        }
+        block_text.clear();
+    };
+
+    for event in Parser::new_ext(markdown, Options::ENABLE_GFM | Options::ENABLE_TABLES) {
+        match event {
+            Event::Start(Tag::Link { dest_url, .. }) => {
+                pending.push((dest_url.to_string(), block_text.chars().count()));
+            }
+            Event::Text(text) | Event::Code(text) => block_text.push_str(&text),
+            Event::SoftBreak | Event::HardBreak => block_text.push(' '),
+            Event::End(
+                TagEnd::Paragraph | TagEnd::Heading(_) | TagEnd::Item | TagEnd::TableCell,
+            ) => flush(&mut block_text, &mut pending),
+            _ => {}
+        }
+    }
+    flush(&mut block_text, &mut pending);
+    snippets
+}
+
+/// Collapses whitespace and, for long blocks, keeps a window of
+/// `SNIPPET_LENGTH` characters around `offset`, cut at word boundaries. The
+/// result is HTML-escaped: it is source text that templates print as is.
+fn snippet_around(text: &str, offset: usize) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let (mut start, mut end) = (0, chars.len());
+    if chars.len() > SNIPPET_LENGTH {
+        start = offset.saturating_sub(SNIPPET_LENGTH / 3).min(chars.len() - SNIPPET_LENGTH);
+        end = start + SNIPPET_LENGTH;
+        if start > 0 {
+            while start < offset && !chars[start - 1].is_whitespace() {
+                start += 1;
+            }
+        }
+        if end < chars.len() {
+            while end > offset && !chars[end].is_whitespace() {
+                end -= 1;
+            }
+        }
+    }
+    let window: String = chars[start..end].iter().collect();
+    let mut snippet = window.split_whitespace().collect::<Vec<_>>().join(" ");
+    if start > 0 {
+        snippet.insert(0, '…');
+    }
+    if end < chars.len() {
+        snippet.push('…');
+    }
+    htmlescape::encode_minimal(&snippet)
 }
 
 fn get_inkjet_language(lang_str: &str) -> Option<Language> {
     LANGUAGE_MAP.get(lang_str.to_lowercase().as_str()).cloned()
 }
 
+type LanguageHints = Vec<(&'static str, Vec<(Regex, u32)>)>;
+
+lazy_static! {
+    /// Telltale patterns per `LANGUAGE_MAP` key, weighted by how specific
+    /// they are to that language.
+    static ref LANGUAGE_HINTS: LanguageHints = {
+        let hints: Vec<(&str, Vec<(&str, u32)>)> = vec![
+            ("rust", vec![
+                (r"\bfn\s+\w+\s*(<[^>]*>)?\(", 2),
+                (r"\blet\s+mut\b", 2),
+                (r"\bimpl\b.*\{", 2),
+                (r"\buse\s+(std|crate|super)::", 3),
+                (r"\b(println|vec|format)!\(", 2),
+                (r"->\s*(Self|Option|Result|&)", 1),
+            ]),
+            ("python", vec![
+                (r"(?m)^\s*def\s+\w+\(.*\)\s*(->.*)?:\s*$", 3),
+                (r"(?m)^\s*(from\s+[\w.]+\s+)?import\s+\w+", 1),
+                (r"(?m)^\s*(elif|except)\b.*:\s*$", 2),
+                (r"\bself\.\w+", 1),
+                (r"(?m)^\s*class\s+\w+(\(.*\))?:\s*$", 3),
+            ]),
+            ("javascript", vec![
+                (r"\bconsole\.log\(", 3),
+                (r"\bfunction\s*\w*\s*\(", 1),
+                (r"\b(const|let)\s+\w+\s*=", 1),
+                (r"=>\s*\{", 1),
+                (r"\bdocument\.\w+", 2),
+                (r"\brequire\(['\x22]", 2),
+            ]),
+            ("typescript", vec![
+                (r"\binterface\s+\w+\s*\{", 3),
+                (r":\s*(string|number|boolean)\b", 2),
+                (r"\btype\s+\w+\s*=", 2),
+            ]),
+            ("go", vec![
+                (r"(?m)^package\s+\w+", 3),
+                (r"\bfunc\s+(\(\w+\s+\*?\w+\)\s*)?\w+\(", 3),
+                (r"\w+\s*:=", 1),
+                (r"\bfmt\.\w+\(", 2),
+            ]),
+            ("c", vec![
+                (r"(?m)^#include\s*[<\x22]", 3),
+                (r"\bint\s+main\s*\(", 2),
+                (r"\bprintf\(", 1),
+            ]),
+            ("cpp", vec![
+                (r"\bstd::\w+", 3),
+                (r"(?m)^#include\s*<(iostream|vector|string|memory)>", 2),
+                (r"\btemplate\s*<", 2),
+            ]),
+            ("java", vec![
+                (r"\bpublic\s+(static\s+)?(class|void)\b", 3),
+                (r"\bSystem\.out\.print", 3),
+            ]),
+            ("html", vec![
+                (r"(?i)<!doctype\s+html", 4),
+                (r"(?i)<(html|head|body|div|span|p|a)\b[^>]*>", 2),
+                (r"</\w+>", 1),
+            ]),
+            ("css", vec![
+                (r"(?m)^\s*[.#]?[\w-]+(\s*[,>+~]?\s*[.#]?[\w-]+)*\s*\{\s*$", 2),
+                (r"(?m)^\s*[\w-]+\s*:\s*[^;]+;\s*$", 2),
+            ]),
+            ("lua", vec![
+                (r"\blocal\s+\w+\s*=", 2),
+                (r"(?m)^\s*end\s*$", 1),
+                (r"\bthen\b", 1),
+            ]),
+            ("nix", vec![
+                (r"\{\s*pkgs\b", 3),
+                (r"\bmkDerivation\b", 3),
+            ]),
+        ];
+        hints
+            .into_iter()
+            .map(|(lang, patterns)| {
+                let patterns = patterns
+                    .into_iter()
+                    .map(|(pattern, weight)| (Regex::new(pattern).unwrap(), weight))
+                    .collect();
+                (lang, patterns)
+            })
+            .collect()
+    };
+}
+
+/// Guesses a `LANGUAGE_MAP` key for an untagged code block. Conservative:
+/// only answers when the best match scores at least 3 and clearly beats the
+/// runner-up, so ambiguous snippets stay plain text.
+fn detect_language(code: &str) -> Option<&'static str> {
+    let mut scores: Vec<(&'static str, u32)> = LANGUAGE_HINTS
+        .iter()
+        .map(|(lang, patterns)| {
+            let score = patterns
+                .iter()
+                .filter(|(regex, _)| regex.is_match(code))
+                .map(|(_, weight)| weight)
+                .sum();
+            (*lang, score)
+        })
+        .collect();
+    scores.sort_by_key(|(_, score)| std::cmp::Reverse(*score));
+    match scores.as_slice() {
+        [(lang, best), (_, runner_up), ..] if *best >= 3 && *best >= runner_up + 2 => Some(lang),
+        _ => None,
+    }
+}
+
 fn extract_language_and_filename(info_string: &str) -> (Option<String>, Option<String>) {
     let parts: Vec<&str> = info_string.split_whitespace().collect();
-    let language = if !parts.is_empty() {
+    let language = if !parts.is_empty() && line_numbers_override(&parts[..1]).is_none() {
         Some(parts[0].to_string())
     } else {
         None
@@ -85,6 +294,16 @@ fn extract_language_and_filename(info_string: &str) -> (Option<String>, Option<S
     (language, filename)
 }
 
+/// Per-block `lines`/`nolines` tokens in the fence info string, which take
+/// precedence over `[content] line_numbers`.
+fn line_numbers_override(parts: &[&str]) -> Option<bool> {
+    parts.iter().rev().find_map(|part| match *part {
+        "lines" => Some(true),
+        "nolines" => Some(false),
+        _ => None,
+    })
+}
+
 fn parse_highlighting_info(info_string: &str) -> (HashSet<usize>, HashSet<usize>, HashSet<usize>) {
     let mut del_lines = HashSet::new();
     let mut add_lines = HashSet::new();
@@ -147,45 +366,64 @@ pub struct TOCEntry {
     id: String,
 }
 
-pub fn extract_frontmatter(content: &str) -> Result<(YamlValue, &str), Box<dyn Error>> {
-    let trimmed_content = content.trim_start();
-    if !trimmed_content.starts_with("---") {
-        return Err("Frontmatter is missing".into());
-    }
-    let end_pattern = "\n---";
-    if let Some(end) = trimmed_content[3..].find(end_pattern) {
-        let frontmatter_end = 3 + end;
-        let frontmatter_str = &trimmed_content[3..frontmatter_end].trim();
-        let frontmatter: YamlValue = serde_yaml::from_str(frontmatter_str)?;
-        if frontmatter.get("title").is_none() || frontmatter.get("date").is_none() {
-            return Err("Missing title or date in frontmatter".into());
-        }
-        if !frontmatter["title"].is_string() || !frontmatter["date"].is_string() {
-            return Err("Title and date must be strings".into());
-        }
-        let md_content = &trimmed_content[frontmatter_end + end_pattern.len()..];
-        Ok((frontmatter, md_content))
-    } else {
-        Err("Frontmatter end delimiter not found".into())
-    }
-}
-
-pub fn markdown_to_html(markdown: &str, file_path: &Path) -> (String, Vec<TOCEntry>) {
-    let mut processed_markdown = process_paths(markdown, file_path);
+pub fn markdown_to_html(
+    markdown: &str,
+    file_path: &Path,
+    config: &Config,
+) -> (String, Vec<TOCEntry>) {
+    let content_config = &config.content;
+    // The excerpt marker only matters to listings and feeds.
+    let markdown = MORE_MARKER.replace(markdown, "");
+    let mut processed_markdown = process_paths(&markdown, file_path);
     processed_markdown = process_wiki_parenthetical_links(&processed_markdown);
 
     let mut options = Options::empty();
     options.insert(Options::ENABLE_GFM);
     options.insert(Options::ENABLE_STRIKETHROUGH);
-    options.insert(Options::ENABLE_MATH);
     options.insert(Options::ENABLE_FOOTNOTES);
     options.insert(Options::ENABLE_TABLES);
     options.insert(Options::ENABLE_TASKLISTS);
     options.insert(Options::ENABLE_DEFINITION_LIST);
-    options.insert(Options::ENABLE_SMART_PUNCTUATION);
+    // Only applies to prose; pulldown-cmark never rewrites code spans or
+    // code block text.
+    if content_config.smart_punctuation {
+        options.insert(Options::ENABLE_SMART_PUNCTUATION);
+    }
+    // Without this, `$` stays literal text, so prices in prose survive.
+    if content_config.math {
+        options.insert(Options::ENABLE_MATH);
+    }
 
     let parser = Parser::new_ext(&processed_markdown, options);
+    let parsed: Vec<Event> = if content_config.sanitize_html {
+        sanitize_events(parser, &content_config.allowed_tags)
+    } else {
+        parser.collect()
+    };
+    let parser = parsed.into_iter().map(|event| match event {
+        Event::InlineMath(tex) => Event::InlineHtml(
+            format!(
+                "<span class=\"math inline\">{}</span>",
+                htmlescape::encode_minimal(&tex)
+            )
+            .into(),
+        ),
+        // Display math sits inside a paragraph, so it gets a span too; KaTeX
+        // renders it as a block from the `display` class.
+        Event::DisplayMath(tex) => Event::InlineHtml(
+            format!(
+                "<span class=\"math display\">{}</span>",
+                htmlescape::encode_minimal(&tex)
+            )
+            .into(),
+        ),
+        _ => event,
+    });
     let highlighter = Mutex::new(Highlighter::new());
+    let code_wrap = content_config.code_wrap;
+    let autodetect_lang = content_config.autodetect_lang;
+    let line_numbers = content_config.line_numbers;
+    let mut current_line_numbers = line_numbers;
 
     let mut in_code_block = false;
     let mut code_content = String::new();
@@ -235,6 +473,10 @@ pub fn markdown_to_html(markdown: &str, file_path: &Path) -> (String, Vec<TOCEnt
                     current_language = lang;
                     current_filename = filename;
                     current_highlighting = parse_highlighting_info(&lang_info);
+                    current_line_numbers = line_numbers_override(
+                        &lang_info.split_whitespace().collect::<Vec<_>>(),
+                    )
+                    .unwrap_or(line_numbers);
                     code_content.clear();
                 }
                 Event::Text(text) if in_code_block => {
@@ -242,6 +484,9 @@ pub fn markdown_to_html(markdown: &str, file_path: &Path) -> (String, Vec<TOCEnt
                 }
                 Event::End(TagEnd::CodeBlock) if in_code_block => {
                     in_code_block = false;
+                    if current_language.is_none() && autodetect_lang {
+                        current_language = detect_language(&code_content).map(str::to_string);
+                    }
                     let highlighted_html = if let Some(lang_str) = current_language.as_ref() {
                         if let Some(inkjet_lang) = get_inkjet_language(lang_str) {
                             match highlighter.lock().unwrap().highlight_to_string(
@@ -251,7 +496,7 @@ pub fn markdown_to_html(markdown: &str, file_path: &Path) -> (String, Vec<TOCEnt
                             ) {
                                 Ok(html) => html,
                                 Err(e) => {
-                                    eprintln!("Error highlighting code: {}", e);
+                                    warn!("Error highlighting code: {}", e);
                                     htmlescape::encode_minimal(&code_content)
                                 }
                             }
@@ -284,6 +529,13 @@ pub fn markdown_to_html(markdown: &str, file_path: &Path) -> (String, Vec<TOCEnt
                             } else if highlight_lines.contains(&line_num) {
                                 line_class = " class=\"highlight\"".to_string();
                             }
+                            if !current_line_numbers {
+                                return format!(
+                                    "<span{line_class}><span class=\"code-line\">{}</span></span>",
+                                    line,
+                                    line_class = line_class
+                                );
+                            }
                             format!(
                                 "<span{line_class}><span class=\"line-number\">{:0width$}</span><span class=\"code-line\">{}</span></span>", 
                                 line_num, 
@@ -293,19 +545,24 @@ pub fn markdown_to_html(markdown: &str, file_path: &Path) -> (String, Vec<TOCEnt
                             )
                         })
                         .collect::<Vec<String>>()
-                        .join("\n");
+                        // Wrapped lines are flex rows, so no newline text
+                        // nodes may sit between them.
+                        .join(if code_wrap { "" } else { "\n" });
+                    let pre_class = if code_wrap { "code-wrap" } else { "code-scroll" };
 
                     let code_html = if let Some(filename) = current_filename.as_ref() {
                         format!(
-                            r#"<div class="code-block"><div class="code-header"><span class="code-filename">{}</span>  <div><span class="code-language">{}</span> <button class="copy-button" onclick="copyCode(this)">copy</button></div></div><pre><code>{}</code></pre></div>"#,
+                            r#"<div class="code-block"><div class="code-header"><span class="code-filename">{}</span>  <div><span class="code-language">{}</span> <button class="copy-button" onclick="copyCode(this)">copy</button></div></div><pre class="{}"><code>{}</code></pre></div>"#,
                             filename,
-                            current_language.as_ref().unwrap().as_str(),
+                            current_language.as_deref().unwrap_or("text"),
+                            pre_class,
                             line_numbered_html
                         )
                     } else {
                         format!(
-                            r#"<div class="code-block"><div class="code-header"> <div><span class="code-language">{}</span><button class="copy-button" onclick="copyCode(this)">copy</button> </div></div><pre><code>{}</code></pre></div>"#,
-                            current_language.as_ref().unwrap().as_str(),
+                            r#"<div class="code-block"><div class="code-header"> <div><span class="code-language">{}</span><button class="copy-button" onclick="copyCode(this)">copy</button> </div></div><pre class="{}"><code>{}</code></pre></div>"#,
+                            current_language.as_deref().unwrap_or("text"),
+                            pre_class,
                             line_numbered_html
                         )
                     };
@@ -323,11 +580,7 @@ pub fn markdown_to_html(markdown: &str, file_path: &Path) -> (String, Vec<TOCEnt
                                 text_content.push_str(t);
                             }
                         }
-                        let slug = text_content
-                            .trim()
-                            .to_lowercase()
-                            .replace(' ', "-")
-                            .replace(|c: char| !c.is_alphanumeric() && c != '-', "");
+                        let slug = slugify(&text_content);
 
                         toc.push(TOCEntry {
                             level,
@@ -337,6 +590,10 @@ pub fn markdown_to_html(markdown: &str, file_path: &Path) -> (String, Vec<TOCEnt
 
                         let mut inner_html = String::new();
                         html::push_html(&mut inner_html, inner_events.into_iter());
+                        // After the TOC entry, so its title stays the bare text.
+                        if content_config.heading_anchors {
+                            inner_html.push_str(&heading_anchor(&slug));
+                        }
                         let heading_html =
                             format!("<h{} id=\"{}\">{}</h{}>", level, slug, inner_html, level);
                         events.push(Event::Html(heading_html.into()));
@@ -356,7 +613,14 @@ pub fn markdown_to_html(markdown: &str, file_path: &Path) -> (String, Vec<TOCEnt
                             }
                         }
                     }
-                    events.push(Event::Start(Tag::Image { link_type, dest_url, title, id }));
+                    let image = Event::Start(Tag::Image { link_type, dest_url, title, id });
+                    match current_heading {
+                        Some((_, ref mut inner_events)) => inner_events.push(image),
+                        None => events.push(image),
+                    }
+                }
+                Event::Start(Tag::DefinitionList) if current_heading.is_none() => {
+                    events.push(Event::Html("<dl class=\"definition-list\">\n".into()));
                 }
                 _ => {
                     if in_code_block {
@@ -373,7 +637,280 @@ pub fn markdown_to_html(markdown: &str, file_path: &Path) -> (String, Vec<TOCEnt
         }
     }
 
+    let events = render_callouts(events);
+    let events = render_task_lists(events, content_config.interactive_tasks);
+    let events = render_footnotes(events);
+    let events = render_tables(events);
+
     let mut html_output = String::new();
     html::push_html(&mut html_output, events.into_iter());
+    if content_config.external_link_icon {
+        html_output = mark_external_links(&html_output, &config.general.base_url);
+    }
     (html_output, toc)
-}
\ No newline at end of file
+}
+
+/// Adds GitHub's `task-list-item`/`task-list-checkbox` classes to task list
+/// items. With `interactive`, checkboxes are left enabled and numbered with
+/// `data-task-index` so a script can persist their state.
+fn render_task_lists(events: Vec<Event>, interactive: bool) -> Vec<Event> {
+    let mut output = Vec::with_capacity(events.len());
+    let mut task_index = 0;
+    for (i, event) in events.iter().enumerate() {
+        match event {
+            Event::Start(Tag::Item) => {
+                // Loose list items wrap their content (marker included) in a paragraph.
+                let is_task = match events.get(i + 1) {
+                    Some(Event::TaskListMarker(_)) => true,
+                    Some(Event::Start(Tag::Paragraph)) => {
+                        matches!(events.get(i + 2), Some(Event::TaskListMarker(_)))
+                    }
+                    _ => false,
+                };
+                if is_task {
+                    output.push(Event::Html("<li class=\"task-list-item\">".into()));
+                } else {
+                    output.push(event.clone());
+                }
+            }
+            Event::TaskListMarker(checked) => {
+                let mut input = String::from("<input type=\"checkbox\" class=\"task-list-checkbox\"");
+                if interactive {
+                    input.push_str(&format!(" data-task-index=\"{}\"", task_index));
+                } else {
+                    input.push_str(" disabled");
+                }
+                if *checked {
+                    input.push_str(" checked");
+                }
+                input.push_str("> ");
+                task_index += 1;
+                output.push(Event::InlineHtml(input.into()));
+            }
+            _ => output.push(event.clone()),
+        }
+    }
+    output
+}
+
+fn footnote_id(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '-' })
+        .collect()
+}
+
+/// Numbers footnote references in order of first use, links each marker to
+/// its definition, and moves the definitions into a trailing
+/// `<section class="footnotes">` with back-references to every marker.
+fn render_footnotes(events: Vec<Event>) -> Vec<Event> {
+    let mut numbers: HashMap<String, usize> = HashMap::new();
+    for event in &events {
+        if let Event::FootnoteReference(name) = event {
+            let next = numbers.len() + 1;
+            numbers.entry(name.to_string()).or_insert(next);
+        }
+    }
+
+    let mut body = Vec::new();
+    let mut definitions: Vec<(String, Vec<Event>)> = Vec::new();
+    let mut current_definition: Option<(String, Vec<Event>)> = None;
+    let mut reference_counts: HashMap<String, usize> = HashMap::new();
+
+    for event in events {
+        let event = match event {
+            Event::Start(Tag::FootnoteDefinition(name)) => {
+                current_definition = Some((name.to_string(), Vec::new()));
+                continue;
+            }
+            Event::End(TagEnd::FootnoteDefinition) => {
+                if let Some(definition) = current_definition.take() {
+                    definitions.push(definition);
+                }
+                continue;
+            }
+            Event::FootnoteReference(name) => {
+                let number = numbers[name.as_ref()];
+                let count = reference_counts.entry(name.to_string()).or_insert(0);
+                *count += 1;
+                let id = footnote_id(&name);
+                Event::InlineHtml(
+                    format!(
+                        "<sup class=\"footnote-ref\" id=\"fnref-{id}-{count}\"><a href=\"#fn-{id}\">{number}</a></sup>",
+                        id = id,
+                        count = count,
+                        number = number
+                    )
+                    .into(),
+                )
+            }
+            other => other,
+        };
+        match current_definition {
+            Some((_, ref mut definition_events)) => definition_events.push(event),
+            None => body.push(event),
+        }
+    }
+
+    if definitions.is_empty() {
+        return body;
+    }
+
+    definitions.sort_by_key(|(name, _)| numbers.get(name).copied().unwrap_or(usize::MAX));
+
+    let mut section = String::from(
+        "<section class=\"footnotes\"><h2 class=\"footnotes-heading\">Footnotes</h2><ol>",
+    );
+    for (name, definition_events) in definitions {
+        let id = footnote_id(&name);
+        let mut definition_html = String::new();
+        html::push_html(&mut definition_html, definition_events.into_iter());
+
+        let backrefs: String = (1..=reference_counts.get(&name).copied().unwrap_or(0))
+            .map(|n| {
+                let marker = if n == 1 { String::new() } else { format!("<sup>{}</sup>", n) };
+                format!(
+                    " <a href=\"#fnref-{id}-{n}\" class=\"footnote-backref\" aria-label=\"Back to reference\">↩{marker}</a>",
+                    id = id,
+                    n = n,
+                    marker = marker
+                )
+            })
+            .collect();
+
+        let trimmed = definition_html.trim_end();
+        let definition_html = match trimmed.strip_suffix("</p>") {
+            Some(inner) => format!("{}{}</p>", inner, backrefs),
+            None => format!("{}{}", trimmed, backrefs),
+        };
+        section.push_str(&format!("<li id=\"fn-{}\">{}</li>", id, definition_html));
+    }
+    section.push_str("</ol></section>");
+
+    body.push(Event::Html(section.into()));
+    body
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::lock_global_state;
+
+    #[test]
+    fn link_snippets_escape_markup_from_the_linking_page() {
+        let snippets = link_snippets("Run `<script>alert(1)</script>` then see [the page](/target).");
+        assert_eq!(snippets.len(), 1);
+        let (dest_url, context) = &snippets[0];
+        assert_eq!(dest_url, "/target");
+        assert!(!context.contains('<'), "unescaped snippet: {}", context);
+        assert!(context.contains("&lt;script&gt;"));
+    }
+
+    #[test]
+    fn link_snippets_escape_inline_html_text() {
+        let snippets = link_snippets("A \\<img src=x onerror=alert(1)> and [link](/a) & more");
+        assert!(snippets[0].1.contains("&lt;img"));
+        assert!(snippets[0].1.contains("&amp; more"));
+    }
+
+    #[test]
+    fn footnotes_are_numbered_by_first_use_with_backrefs() {
+        let markdown = "One[^b], two[^a] and one again[^b].\n\n[^a]: Alpha note.\n\n[^b]: Beta note.\n";
+        let events = render_footnotes(Parser::new_ext(markdown, Options::ENABLE_FOOTNOTES).collect());
+        let mut output = String::new();
+        html::push_html(&mut output, events.into_iter());
+        assert_eq!(
+            output,
+            concat!(
+                "<p>One<sup class=\"footnote-ref\" id=\"fnref-b-1\"><a href=\"#fn-b\">1</a></sup>, ",
+                "two<sup class=\"footnote-ref\" id=\"fnref-a-1\"><a href=\"#fn-a\">2</a></sup> ",
+                "and one again<sup class=\"footnote-ref\" id=\"fnref-b-2\"><a href=\"#fn-b\">1</a></sup>.</p>\n",
+                "<section class=\"footnotes\"><h2 class=\"footnotes-heading\">Footnotes</h2><ol>",
+                "<li id=\"fn-b\"><p>Beta note.",
+                " <a href=\"#fnref-b-1\" class=\"footnote-backref\" aria-label=\"Back to reference\">↩</a>",
+                " <a href=\"#fnref-b-2\" class=\"footnote-backref\" aria-label=\"Back to reference\">↩<sup>2</sup></a>",
+                "</p></li>",
+                "<li id=\"fn-a\"><p>Alpha note.",
+                " <a href=\"#fnref-a-1\" class=\"footnote-backref\" aria-label=\"Back to reference\">↩</a>",
+                "</p></li>",
+                "</ol></section>",
+            )
+        );
+    }
+
+    fn config_with_math(math: bool) -> Config {
+        let mut config = example_config();
+        config.content.math = math;
+        config
+    }
+
+    fn example_config() -> Config {
+        toml::from_str(include_str!("../Config.example.toml")).unwrap()
+    }
+
+    #[test]
+    fn dollar_signs_stay_literal_without_math() {
+        let _lock = lock_global_state();
+        let (html, _) =
+            markdown_to_html("costs $5 and $10", Path::new("page.md"), &config_with_math(false));
+        assert!(html.contains("costs $5 and $10"), "got: {}", html);
+        assert!(!html.contains("class=\"math"));
+    }
+
+    #[test]
+    fn dollar_signs_render_math_when_enabled() {
+        let _lock = lock_global_state();
+        let (html, _) =
+            markdown_to_html("area $x^2$ of a < b", Path::new("page.md"), &config_with_math(true));
+        assert_eq!(html, "<p>area <span class=\"math inline\">x^2</span> of a &lt; b</p>\n");
+        let (html, _) = markdown_to_html(
+            "Sum:\n\n$$\\sum_{i<n} i$$\n",
+            Path::new("page.md"),
+            &config_with_math(true),
+        );
+        assert_eq!(
+            html,
+            "<p>Sum:</p>\n<p><span class=\"math display\">\\sum_{i&lt;n} i</span></p>\n"
+        );
+    }
+
+    #[test]
+    fn definition_lists_round_trip() {
+        let _lock = lock_global_state();
+        let markdown = "Term\n: First definition\n: Second definition\n\nOther term\n: More\n";
+        let (html, _) = markdown_to_html(markdown, Path::new("page.md"), &example_config());
+        assert_eq!(
+            html.trim(),
+            "<dl class=\"definition-list\">\n<dt>Term</dt>\n<dd>First definition</dd>\n\
+             <dd>Second definition</dd>\n<dt>Other term</dt>\n<dd>More</dd>\n</dl>"
+        );
+    }
+
+    #[test]
+    fn definition_list_terms_can_hold_inline_markup() {
+        let _lock = lock_global_state();
+        let markdown = "`cargo` *tool*\n: Builds **crates**\n";
+        let (html, _) = markdown_to_html(markdown, Path::new("page.md"), &example_config());
+        assert!(html.contains("<dt><code>cargo</code> <em>tool</em></dt>"), "{}", html);
+        assert!(html.contains("<dd>Builds <strong>crates</strong></dd>"), "{}", html);
+    }
+
+    #[test]
+    fn smart_punctuation_leaves_code_alone() {
+        let _lock = lock_global_state();
+        let markdown = "Say \"hi\" -- run `--flag \"x\"`.\n\n```sh\necho \"a\" -- 'b'\n```\n";
+        let (html, _) = markdown_to_html(markdown, Path::new("page.md"), &example_config());
+        assert!(html.contains("Say \u{201c}hi\u{201d} \u{2013} run"), "{}", html);
+        assert!(html.contains("<code>--flag \"x\"</code>"), "{}", html);
+        assert!(html.contains("echo &quot;a&quot; -- &#x27;b&#x27;"), "{}", html);
+        assert!(!html.contains('\u{2018}') && !html.contains('\u{2019}'), "{}", html);
+    }
+
+    #[test]
+    fn smart_punctuation_can_be_turned_off() {
+        let _lock = lock_global_state();
+        let mut config = example_config();
+        config.content.smart_punctuation = false;
+        let (html, _) = markdown_to_html("Say \"hi\" -- bye", Path::new("page.md"), &config);
+        assert!(html.contains("Say \"hi\" -- bye"), "{}", html);
+    }
+}
@@ -0,0 +1,124 @@
+use crate::logger::debug;
+use crate::file_ops::{CACHE_DIR, create_directory_safely};
+use crate::utils::content_hash;
+use ab_glyph::{FontRef, PxScale};
+use colored::Colorize;
+use image::{Rgba, RgbaImage};
+use imageproc::drawing::{draw_text_mut, text_size};
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const OG_WIDTH: u32 = 1200;
+const OG_HEIGHT: u32 = 630;
+const OG_MARGIN: u32 = 80;
+const OG_FONT: &[u8] = include_bytes!("../assets/fonts/DejaVuSans-Bold.ttf");
+
+/// Returns where the Open Graph image for `route` is written. The path
+/// mirrors the route, so `/a/b/` and `/a-b/` get distinct files.
+pub fn og_image_path(route: &str, dist_static: &Path) -> PathBuf {
+    match route.trim_matches('/') {
+        "" => dist_static.join("og").join("index.png"),
+        trimmed => dist_static.join("og").join(format!("{}.png", trimmed)),
+    }
+}
+
+/// Renders a 1200×630 card with the page title and site name to
+/// [`og_image_path`] and returns that path. Renders are cached by content
+/// hash so unchanged titles are only copied.
+pub fn generate_og_image(
+    title: &str,
+    site_name: &str,
+    route: &str,
+    dist_static: &Path,
+) -> Result<PathBuf, Box<dyn Error>> {
+    let output_path = og_image_path(route, dist_static);
+    if let Some(parent) = output_path.parent() {
+        create_directory_safely(parent)?;
+    }
+
+    let key = content_hash(format!("{}\0{}", title, site_name).as_bytes());
+    let cache_dir = Path::new(CACHE_DIR).join("og");
+    let cached_path = cache_dir.join(format!("{}.png", key));
+
+    if !cached_path.exists() {
+        create_directory_safely(&cache_dir)?;
+        render_og_image(title, site_name)?.save(&cached_path)?;
+        debug!(
+            "{} {}",
+            "Rendered Open Graph image for".green(),
+            route.yellow()
+        );
+    }
+    fs::copy(&cached_path, &output_path)?;
+
+    Ok(output_path)
+}
+
+fn render_og_image(title: &str, site_name: &str) -> Result<RgbaImage, Box<dyn Error>> {
+    let font = FontRef::try_from_slice(OG_FONT)?;
+    let mut img = RgbaImage::from_pixel(OG_WIDTH, OG_HEIGHT, Rgba([30, 30, 46, 255]));
+
+    let title_scale = PxScale::from(72.0);
+    let line_height = 90;
+    let lines = wrap_text(title, &font, title_scale, OG_WIDTH - 2 * OG_MARGIN);
+    let mut y = OG_MARGIN as i32;
+    for line in lines.iter().take(4) {
+        draw_text_mut(
+            &mut img,
+            Rgba([205, 214, 244, 255]),
+            OG_MARGIN as i32,
+            y,
+            title_scale,
+            &font,
+            line,
+        );
+        y += line_height;
+    }
+
+    draw_text_mut(
+        &mut img,
+        Rgba([147, 153, 178, 255]),
+        OG_MARGIN as i32,
+        (OG_HEIGHT - OG_MARGIN - 40) as i32,
+        PxScale::from(40.0),
+        &font,
+        site_name,
+    );
+
+    Ok(img)
+}
+
+fn wrap_text(text: &str, font: &FontRef, scale: PxScale, max_width: u32) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut current = String::new();
+    for word in text.split_whitespace() {
+        let candidate = if current.is_empty() {
+            word.to_string()
+        } else {
+            format!("{} {}", current, word)
+        };
+        if !current.is_empty() && text_size(scale, font, &candidate).0 > max_width {
+            lines.push(std::mem::replace(&mut current, word.to_string()));
+        } else {
+            current = candidate;
+        }
+    }
+    if !current.is_empty() {
+        lines.push(current);
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn og_image_paths_follow_the_route() {
+        let dist_static = Path::new("dist/static");
+        assert_eq!(og_image_path("/", dist_static), dist_static.join("og/index.png"));
+        assert_eq!(og_image_path("/a/b/", dist_static), dist_static.join("og/a/b.png"));
+        assert_ne!(og_image_path("/a/b/", dist_static), og_image_path("/a-b/", dist_static));
+    }
+}
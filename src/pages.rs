@@ -0,0 +1,101 @@
+use crate::logger::{debug, warn};
+use crate::config::Config;
+use crate::frontmatter::{Frontmatter, extract_frontmatter};
+use crate::markdown::{TOCEntry, markdown_to_html};
+use crate::utils::is_not_hidden_dir;
+use colored::Colorize;
+use std::cell::OnceCell;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// A markdown page under `content/`, read and parsed once per build.
+pub struct Page {
+    /// Markdown file under `content/`.
+    pub path: PathBuf,
+    pub frontmatter: Frontmatter,
+    /// The markdown after the frontmatter.
+    pub markdown: String,
+    rendered: OnceCell<(String, Vec<TOCEntry>)>,
+}
+
+impl Page {
+    /// `markdown` through `markdown_to_html`, rendered on first use and
+    /// shared by the page, its feed item and its listing excerpt. Links are
+    /// resolved when rendering, so the permalinks must be set up first.
+    pub fn rendered(&self, config: &Config) -> &(String, Vec<TOCEntry>) {
+        self.rendered
+            .get_or_init(|| markdown_to_html(&self.markdown, &self.path, config))
+    }
+}
+
+/// Every page a build renders, in `content/` order.
+#[derive(Default)]
+pub struct Pages {
+    pages: Vec<Page>,
+    by_path: HashMap<PathBuf, usize>,
+}
+
+impl Pages {
+    /// Reads every markdown file under `content/`. Drafts are left out
+    /// unless `drafts`; so are pages whose frontmatter doesn't parse, with a
+    /// warning, or the load fails when `strict`.
+    pub fn load(drafts: bool, strict: bool) -> Result<Pages, Box<dyn Error>> {
+        let mut pages = Pages::default();
+        for entry in WalkDir::new("content")
+            .sort_by_file_name()
+            .into_iter()
+            .filter_entry(is_not_hidden_dir)
+            .filter_map(|e| e.ok())
+        {
+            let path = entry.path();
+            if !entry.file_type().is_file() || path.extension().and_then(|s| s.to_str()) != Some("md") {
+                continue;
+            }
+            let content = fs::read_to_string(path)?;
+            let (frontmatter, markdown) = match extract_frontmatter(&content) {
+                Ok(parsed) => parsed,
+                Err(e) if strict => {
+                    return Err(format!("Invalid frontmatter in {}: {}", path.display(), e).into());
+                }
+                Err(e) => {
+                    warn!(
+                        "{} {}: invalid frontmatter: {} (not rendered)",
+                        "Skipping".yellow(),
+                        path.display().to_string().replace('\\', "/"),
+                        e
+                    );
+                    continue;
+                }
+            };
+            if frontmatter.draft && !drafts {
+                debug!(
+                    "{} {} (draft)",
+                    "Skipping".green(),
+                    path.display().to_string().replace('\\', "/").yellow()
+                );
+                continue;
+            }
+            pages.by_path.insert(path.to_path_buf(), pages.pages.len());
+            pages.pages.push(Page {
+                path: path.to_path_buf(),
+                markdown: markdown.to_string(),
+                frontmatter,
+                rendered: OnceCell::new(),
+            });
+        }
+        Ok(pages)
+    }
+
+    /// The page read from `path`, or `None` when it was left out (or isn't
+    /// a page).
+    pub fn get(&self, path: &Path) -> Option<&Page> {
+        self.by_path.get(path).map(|&index| &self.pages[index])
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Page> {
+        self.pages.iter()
+    }
+}
@@ -1,14 +1,18 @@
 use lazy_static::lazy_static;
 use regex::Regex;
+use pulldown_cmark::{Event, Options, Parser, Tag};
 use std::{
     collections::HashMap,
+    error::Error,
+    ops::Range,
     path::{Path, PathBuf},
     sync::RwLock,
+    sync::atomic::{AtomicBool, Ordering},
 };
 use std::sync::Mutex;
 use walkdir::WalkDir;
 
-use crate::utils::sanitize_filename;
+use crate::{frontmatter::Frontmatter, utils::sanitize_filename};
 
 lazy_static! {
     static ref FILE_CACHE: RwLock<Option<HashMap<String, Vec<PathBuf>>>> = RwLock::new(None);
@@ -16,41 +20,215 @@ lazy_static! {
     static ref ALT_IMAGE_REGEX: Regex = Regex::new(r"!\[\[([^|\]]+)(?:\|([^\]]*))?\]\]").unwrap();
     static ref LINK_REGEX: Regex = Regex::new(r"\[\[([^|\]]+)(?:\|([^\]]*))?\]\]").unwrap();
     static ref WIKI_LINK_REGEX: Regex = Regex::new(r"\[(.*?)\]\(wiki:([^)]+)\)").unwrap();
+    /// `[text](destination "title")`, or an image (`![...]`, left alone).
+    static ref STANDARD_LINK_REGEX: Regex = Regex::new(r"!?\[([^\]]*)\]\(([^)\s]+)([^)]*)\)").unwrap();
     pub static ref STATIC_FILE_MAP: Mutex<HashMap<String, PathBuf>> = Mutex::new(HashMap::new());
+    static ref PERMALINKS: RwLock<HashMap<PathBuf, String>> = RwLock::new(HashMap::new());
 }
 
+static FLATTEN_STATIC: AtomicBool = AtomicBool::new(true);
+
+pub fn set_flatten_static(flatten: bool) {
+    FLATTEN_STATIC.store(flatten, Ordering::Relaxed);
+}
+
+/// Path under `static/` for a content asset given relative to `content/`:
+/// either a single flattened file name or the original relative path.
+pub fn static_asset_name(relative_path: &str) -> String {
+    if FLATTEN_STATIC.load(Ordering::Relaxed) {
+        sanitize_filename(relative_path)
+    } else {
+        relative_path.replace('\\', "/")
+    }
+}
+
+/// Indexes every file under `content/` by file name (and pages also by
+/// stem). Rebuilt on each call, so `serve` sees files added, renamed or
+/// removed since the last build.
 pub fn init_file_cache() {
-    let mut cache = FILE_CACHE.write().unwrap();
-    if cache.is_none() {
-        let mut file_map: HashMap<String, Vec<PathBuf>> = HashMap::new();
-
-        for entry in WalkDir::new("content").into_iter().filter_map(|e| e.ok()) {
-            if entry.file_type().is_file() {
-                let filename = entry.file_name().to_string_lossy().to_string();
-                let filename_clone = filename.clone();
-                let file_path = entry.path().to_path_buf();
-
-                file_map
-                    .entry(filename)
-                    .or_insert_with(Vec::new)
-                    .push(file_path.clone());
-
-                if filename_clone.ends_with(".md") {
-                    if let Some(stem) = entry.path().file_stem() {
-                        let stem_str = stem.to_string_lossy().to_string();
-                        file_map
-                            .entry(stem_str)
-                            .or_insert_with(Vec::new)
-                            .push(file_path);
-                    }
+    let mut file_map: HashMap<String, Vec<PathBuf>> = HashMap::new();
+
+    for entry in WalkDir::new("content").sort_by_file_name().into_iter().filter_map(|e| e.ok()) {
+        if entry.file_type().is_file() {
+            let filename = entry.file_name().to_string_lossy().to_string();
+            let filename_clone = filename.clone();
+            let file_path = entry.path().to_path_buf();
+
+            file_map
+                .entry(filename)
+                .or_insert_with(Vec::new)
+                .push(file_path.clone());
+
+            if filename_clone.ends_with(".md") {
+                if let Some(stem) = entry.path().file_stem() {
+                    let stem_str = stem.to_string_lossy().to_string();
+                    file_map
+                        .entry(stem_str)
+                        .or_insert_with(Vec::new)
+                        .push(file_path);
                 }
             }
         }
+    }
+
+    *FILE_CACHE.write().unwrap() = Some(file_map);
+}
+
+/// Records the routes `permalink` and `slug` frontmatter give `pages` (each
+/// markdown file with its parsed frontmatter), failing on an invalid one or
+/// on two pages claiming the same route.
+pub fn init_permalinks<'a>(
+    pages: impl IntoIterator<Item = (&'a Path, &'a Frontmatter)>,
+) -> Result<(), Box<dyn Error>> {
+    let mut permalinks = HashMap::new();
+    let mut claimed: HashMap<String, PathBuf> = HashMap::new();
+
+    for (path, frontmatter) in pages {
+        let relative_path = path
+            .strip_prefix("content")?
+            .to_string_lossy()
+            .replace('\\', "/");
+        let mut route = default_route(&relative_path);
+
+        if let Some(permalink) = &frontmatter.permalink {
+            check_route_segments(permalink)
+                .map_err(|e| format!("Invalid permalink in {}: {}", path.display(), e))?;
+            route = normalize_route(permalink);
+            permalinks.insert(path.to_path_buf(), route.clone());
+        } else if let Some(slug) = &frontmatter.slug {
+            check_route_segments(slug)
+                .map_err(|e| format!("Invalid slug in {}: {}", path.display(), e))?;
+            let parent = Path::new(&relative_path)
+                .parent()
+                .map(|p| p.to_string_lossy().to_string())
+                .unwrap_or_default();
+            route = normalize_route(&format!("{}/{}", parent, slug));
+            permalinks.insert(path.to_path_buf(), route.clone());
+        }
+
+        if let Some(other) = claimed.get(&route) {
+            return Err(format!(
+                "Duplicate permalink '{}' claimed by {} and {}",
+                route,
+                other.display().to_string().replace('\\', "/"),
+                path.display().to_string().replace('\\', "/")
+            )
+            .into());
+        }
+        claimed.insert(route, path.to_path_buf());
+    }
+
+    *PERMALINKS.write().unwrap() = permalinks;
+    Ok(())
+}
 
-        *cache = Some(file_map);
+/// Route a markdown file is published at when no permalink overrides it,
+/// given its path relative to `content/`. An `index.md` is published as its
+/// directory's page.
+pub fn default_route(relative_path: &str) -> String {
+    let clean_path = relative_path.strip_suffix(".md").unwrap_or(relative_path);
+    if clean_path == "index" {
+        "/".to_string()
+    } else {
+        format!("/{}", clean_path.strip_suffix("/index").unwrap_or(clean_path))
     }
 }
 
+/// What is published at a content directory's route.
+pub enum DirectoryPage {
+    /// The directory's `index.md`, rendered like any other page.
+    Index(PathBuf),
+    /// A generated listing of the directory's files.
+    Listing,
+}
+
+/// Decides what a directory under `content/` publishes at its route: its
+/// `index.md` when it has one that isn't moved elsewhere by a permalink,
+/// otherwise a generated listing. `content/` itself is never listed, so
+/// this is only meaningful for subdirectories.
+pub fn directory_page(dir: &Path) -> DirectoryPage {
+    let index = dir.join("index.md");
+    if !index.is_file() {
+        return DirectoryPage::Listing;
+    }
+    let dir_route = default_route(
+        &dir.strip_prefix("content")
+            .unwrap_or(dir)
+            .join("index.md")
+            .to_string_lossy()
+            .replace('\\', "/"),
+    );
+    if route_for(&index) == dir_route {
+        DirectoryPage::Index(index)
+    } else {
+        DirectoryPage::Listing
+    }
+}
+
+/// Route a markdown file under `content/` is published at, honoring
+/// `permalink`/`slug` frontmatter.
+pub fn route_for(content_path: &Path) -> String {
+    if let Some(route) = PERMALINKS.read().unwrap().get(content_path) {
+        return route.clone();
+    }
+    let relative_path = content_path
+        .strip_prefix("content")
+        .unwrap_or(content_path)
+        .to_string_lossy()
+        .replace('\\', "/");
+    default_route(&relative_path)
+}
+
+/// Rejects `.`, `..` and empty segments, which would otherwise let a
+/// frontmatter route escape `dist/` or silently collapse into another one.
+/// Leading and trailing slashes are fine.
+fn check_route_segments(route: &str) -> Result<(), String> {
+    let trimmed = route.trim_matches('/');
+    if trimmed.is_empty() {
+        return Ok(());
+    }
+    match trimmed.split('/').find(|segment| matches!(segment.trim(), "" | "." | "..")) {
+        Some(segment) => Err(format!("'{}' has an invalid segment '{}'", route, segment)),
+        None => Ok(()),
+    }
+}
+
+pub fn normalize_route(route: &str) -> String {
+    let segments: Vec<&str> = route
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .collect();
+    format!("/{}", segments.join("/"))
+}
+
+/// Case-insensitive key for a route, used to match backlinks to pages.
+pub fn route_key(route: &str) -> String {
+    normalize_route(route).to_lowercase()
+}
+
+/// Key of the page a link in processed markdown points at, resolving `.md`
+/// destinations the same way `[[...]]` links are. External links, anchors
+/// and static files return `None`.
+pub fn link_target_key(dest_url: &str) -> Option<String> {
+    if dest_url.contains("://")
+        || dest_url.starts_with("mailto:")
+        || dest_url.starts_with("wiki:")
+        || dest_url.starts_with("/static/")
+    {
+        return None;
+    }
+    let path = dest_url.split(['#', '?']).next().unwrap_or_default();
+    if path.is_empty() {
+        return None;
+    }
+    let route = if path.ends_with(".md") {
+        get_internal_link_path(path.trim_start_matches('/'))
+    } else {
+        path.to_string()
+    };
+    Some(route_key(&route))
+}
+
 pub fn process_paths(markdown: &str, current_path: &Path) -> String {
     if FILE_CACHE.read().unwrap().is_none() {
         init_file_cache();
@@ -59,10 +237,76 @@ pub fn process_paths(markdown: &str, current_path: &Path) -> String {
     let markdown = process_standard_images(markdown, current_path);
     let markdown = process_alternative_images(&markdown, current_path);
     let markdown = process_links(&markdown);
+    let markdown = process_asset_links(&markdown, current_path);
     let markdown = process_wiki_parenthetical_links(&markdown);
     markdown
 }
 
+/// Points `[text](spec.pdf)` links to files in `content/` that aren't pages
+/// at their copy under `/static/`, resolved the way images are. Links in
+/// code spans and code blocks are left as written.
+pub fn process_asset_links(markdown: &str, current_path: &Path) -> String {
+    rewrite_asset_links(markdown, current_path, Path::new("content"))
+}
+
+fn rewrite_asset_links(markdown: &str, current_path: &Path, content_dir: &Path) -> String {
+    let code = code_ranges(markdown);
+    STANDARD_LINK_REGEX
+        .replace_all(markdown, |caps: &regex::Captures| {
+            let whole = caps.get(0).unwrap();
+            let in_code = code.iter().any(|range| range.contains(&whole.start()));
+            if whole.as_str().starts_with('!') || in_code {
+                return whole.as_str().to_string();
+            }
+            match asset_link(&caps[2], current_path, content_dir) {
+                Some(url) => format!("[{}]({}{})", &caps[1], url, &caps[3]),
+                None => whole.as_str().to_string(),
+            }
+        })
+        .to_string()
+}
+
+/// Byte ranges of the code spans and code blocks in `markdown`.
+fn code_ranges(markdown: &str) -> Vec<Range<usize>> {
+    Parser::new_ext(markdown, Options::ENABLE_GFM | Options::ENABLE_TABLES)
+        .into_offset_iter()
+        .filter_map(|(event, range)| match event {
+            Event::Code(_) | Event::Start(Tag::CodeBlock(_)) => Some(range),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The `/static/` URL of a relative link to a file in `content_dir` other
+/// than a page (any extension but `.md`/`.html`), keeping its
+/// `#fragment`/`?query`. `None` when no such file exists, so links to pages
+/// with a dot in their name (`notes/2024.01`) stay page links.
+fn asset_link(destination: &str, current_path: &Path, content_dir: &Path) -> Option<String> {
+    if destination.contains("://")
+        || destination.starts_with('/')
+        || destination.starts_with('#')
+        || destination.starts_with("mailto:")
+        || destination.starts_with("wiki:")
+    {
+        return None;
+    }
+    let split = destination.find(['#', '?']).unwrap_or(destination.len());
+    let (path, suffix) = destination.split_at(split);
+    let extension = Path::new(path).extension()?.to_string_lossy().to_lowercase();
+    if ["md", "html", "htm"].contains(&extension.as_str()) {
+        return None;
+    }
+    if path.contains('/') {
+        let relative_path = content_relative_path(path, current_path);
+        if !content_dir.join(&relative_path).is_file() {
+            return None;
+        }
+        return Some(format!("/static/{}{}", static_asset_name(&relative_path), suffix));
+    }
+    FILE_CACHE.read().unwrap().as_ref()?.get(path)?;
+    Some(format!("{}{}", find_unique_image(path, current_path), suffix))
+}
+
 pub fn process_standard_images(markdown: &str, current_path: &Path) -> String {
     IMAGE_REGEX
         .replace_all(markdown, |caps: &regex::Captures| {
@@ -82,20 +326,50 @@ pub fn process_standard_images(markdown: &str, current_path: &Path) -> String {
         .to_string()
 }
 
+/// Obsidian-style size suffix of a wiki embed: `300` (width) or `300x200`.
+fn parse_image_size(value: &str) -> Option<(u32, Option<u32>)> {
+    match value.trim().split_once('x') {
+        Some((width, height)) => Some((width.parse().ok()?, Some(height.parse().ok()?))),
+        None => Some((value.trim().parse().ok()?, None)),
+    }
+}
+
 pub fn process_alternative_images(markdown: &str, current_path: &Path) -> String {
     ALT_IMAGE_REGEX
         .replace_all(markdown, |caps: &regex::Captures| {
             let path = &caps[1];
-            let alt_text = caps.get(2).map_or("", |m| m.as_str());
+            let options = caps.get(2).map_or("", |m| m.as_str());
+            // `![[img.png|alt|300]]`: a trailing numeric part is the size.
+            let (alt_text, size) = match options.rsplit_once('|') {
+                Some((alt, last)) => match parse_image_size(last) {
+                    Some(size) => (alt, Some(size)),
+                    None => (options, None),
+                },
+                None => match parse_image_size(options) {
+                    Some(size) => ("", Some(size)),
+                    None => (options, None),
+                },
+            };
 
-            if !path.starts_with("http://")
+            let src = if !path.starts_with("http://")
                 && !path.starts_with("https://")
                 && !path.starts_with('/')
             {
-                let static_path = find_unique_image(path, current_path);
-                format!("![{}]({})", alt_text, static_path)
+                find_unique_image(path, current_path)
             } else {
-                format!("![{}]({})", alt_text, path)
+                path.to_string()
+            };
+
+            match size {
+                // Markdown image syntax can't carry a size, so emit the tag.
+                Some((width, height)) => format!(
+                    r#"<img src="{}" alt="{}" width="{}"{}>"#,
+                    src,
+                    htmlescape::encode_attribute(alt_text),
+                    width,
+                    height.map_or(String::new(), |h| format!(r#" height="{}""#, h))
+                ),
+                None => format!("![{}]({})", alt_text, src),
             }
         })
         .to_string()
@@ -128,6 +402,9 @@ pub fn process_links(markdown: &str) -> String {
             {
                 let link_path = if !path.contains('/') {
                     find_unique_internal_link(path)
+                } else if !path.ends_with(".md") && Path::new("content").join(path).is_file() {
+                    // `[[docs/spec.pdf]]` is a file, not a page.
+                    format!("/static/{}", static_asset_name(path))
                 } else {
                     get_internal_link_path(path)
                 };
@@ -166,7 +443,7 @@ pub fn find_unique_image(image_name: &str, current_path: &Path) -> String {
                     let path = &matches[0];
                     format!(
                         "/static/{}",
-                        sanitize_filename(
+                        static_asset_name(
                             &path
                                 .strip_prefix("content")
                                 .unwrap_or(path)
@@ -178,7 +455,7 @@ pub fn find_unique_image(image_name: &str, current_path: &Path) -> String {
                     let path = &matches[0]; // Just take the first one
                     format!(
                         "/static/{}",
-                        sanitize_filename(
+                        static_asset_name(
                             &path
                                 .strip_prefix("content")
                                 .unwrap_or(path)
@@ -195,6 +472,33 @@ pub fn find_unique_image(image_name: &str, current_path: &Path) -> String {
     }
 }
 
+/// `[[name]]` links in `markdown` whose bare name matches more than one page;
+/// `find_unique_internal_link` silently picks the first of them.
+pub fn ambiguous_wiki_links(markdown: &str) -> Vec<(String, Vec<PathBuf>)> {
+    let cache = FILE_CACHE.read().unwrap();
+    let Some(file_map) = &*cache else {
+        return Vec::new();
+    };
+    LINK_REGEX
+        .captures_iter(markdown)
+        .filter_map(|caps| {
+            let name = caps[1].trim();
+            if name.starts_with("wiki:") || name.contains('/') {
+                return None;
+            }
+            let mut pages: Vec<PathBuf> = file_map
+                .get(name)?
+                .iter()
+                .filter(|path| path.extension().and_then(|s| s.to_str()) == Some("md"))
+                .cloned()
+                .collect();
+            pages.sort();
+            pages.dedup();
+            (pages.len() > 1).then(|| (name.to_string(), pages))
+        })
+        .collect()
+}
+
 pub fn find_unique_internal_link(link_name: &str) -> String {
     let cache = FILE_CACHE.read().unwrap();
     if let Some(file_map) = &*cache {
@@ -205,19 +509,10 @@ pub fn find_unique_internal_link(link_name: &str) -> String {
                 .or_else(|| matches.first());
             if let Some(match_path) = match_path {
                 if match_path.to_string_lossy().ends_with(".md") {
-                    let path = match_path
-                        .strip_prefix("content")
-                        .unwrap_or(match_path)
-                        .with_extension("");
-                    let clean_path = path.to_string_lossy().replace('\\', "/");
-                    if clean_path == "index" {
-                        "/".to_string()
-                    } else {
-                        format!("/{}", clean_path)
-                    }
+                    route_for(match_path)
                 } else {
                     let relative_path = match_path.strip_prefix("content").unwrap_or(match_path);
-                    let sanitized_name = sanitize_filename(&relative_path.to_string_lossy());
+                    let sanitized_name = static_asset_name(&relative_path.to_string_lossy());
                     format!("/static/{}", sanitized_name)
                 }
             } else {
@@ -232,26 +527,29 @@ pub fn find_unique_internal_link(link_name: &str) -> String {
 }
 
 pub fn get_internal_link_path(path: &str) -> String {
-    let clean_path = if path.ends_with(".md") {
-        &path[0..path.len() - 3]
-    } else {
-        path
-    };
-    if clean_path == "index" {
-        "/".to_string()
-    } else {
-        format!("/{}", clean_path)
+    let clean_path = path.strip_suffix(".md").unwrap_or(path);
+    let content_path = Path::new("content").join(format!("{}.md", clean_path));
+    if let Some(route) = PERMALINKS.read().unwrap().get(&content_path) {
+        return route.clone();
     }
+    default_route(clean_path)
 }
 
 pub fn resolve_path(path: &str, current_path: &Path) -> String {
+    format!("/static/{}", static_asset_name(&content_relative_path(path, current_path)))
+}
+
+/// A link or image path from the page at `current_path` as a path relative
+/// to `content/`: `./` and `../` paths start at the page's directory, others
+/// at `content/` itself.
+fn content_relative_path(path: &str, current_path: &Path) -> String {
     let current_dir = current_path
         .parent()
         .unwrap_or(Path::new(""))
         .strip_prefix("content")
         .unwrap_or(Path::new(""));
 
-    let relative_path = if path.starts_with("./") || path.starts_with("../") {
+    if path.starts_with("./") || path.starts_with("../") {
         let mut full_path = PathBuf::from(current_dir);
         let path_segments: Vec<&str> = path.split('/').collect();
         let mut path_iter = path_segments.iter();
@@ -274,7 +572,163 @@ pub fn resolve_path(path: &str, current_path: &Path) -> String {
         full_path.to_string_lossy().to_string()
     } else {
         path.to_string()
-    };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::markdown::link_snippets;
+    use crate::utils::lock_global_state;
+    use std::fs;
+
+    fn spec_url() -> String {
+        format!("/static/{}", static_asset_name("docs/spec.pdf"))
+    }
+
+    /// A `content/` directory holding `docs/spec.pdf`, unique to one test.
+    fn content_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("sekiei-{}-{}", name, std::process::id()));
+        fs::create_dir_all(dir.join("docs")).unwrap();
+        fs::create_dir_all(dir.join("notes")).unwrap();
+        fs::write(dir.join("docs/spec.pdf"), b"%PDF-1.4").unwrap();
+        dir
+    }
+
+    #[test]
+    fn pdf_links_point_at_the_static_copy() {
+        let _lock = lock_global_state();
+        let dir = content_dir("pdf-links");
+        let page = Path::new("content/notes/idea.md");
+        assert_eq!(
+            rewrite_asset_links("See [spec](docs/spec.pdf \"Spec\").", page, &dir),
+            format!("See [spec]({} \"Spec\").", spec_url())
+        );
+        assert_eq!(
+            rewrite_asset_links("[p2](../docs/spec.pdf#page=2)", page, &dir),
+            format!("[p2]({}#page=2)", spec_url())
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn bare_pdf_names_resolve_through_the_file_cache() {
+        let _lock = lock_global_state();
+        let dir = content_dir("pdf-cache");
+        *FILE_CACHE.write().unwrap() = Some(HashMap::from([(
+            "spec.pdf".to_string(),
+            vec![PathBuf::from("content/docs/spec.pdf")],
+        )]));
+        let page = Path::new("content/notes/idea.md");
+        assert_eq!(
+            rewrite_asset_links("[spec](spec.pdf) [gone](gone.pdf)", page, &dir),
+            format!("[spec]({}) [gone](gone.pdf)", spec_url())
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn adjacent_links_are_all_rewritten() {
+        let _lock = lock_global_state();
+        let dir = content_dir("adjacent");
+        let page = Path::new("content/index.md");
+        assert_eq!(
+            rewrite_asset_links("[a](docs/spec.pdf)[b](docs/spec.pdf)", page, &dir),
+            format!("[a]({0})[b]({0})", spec_url())
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn missing_files_pages_images_and_code_are_left_alone() {
+        let _lock = lock_global_state();
+        let dir = content_dir("left-alone");
+        let page = Path::new("content/index.md");
+        for markdown in [
+            "[notes](notes/2024.01)",
+            "[v](guide/v1.2)",
+            "[page](notes/idea.md)",
+            "![img](docs/spec.pdf)",
+            "[web](https://example.com/a.pdf)",
+            "`[spec](docs/spec.pdf)`",
+            "```\n[spec](docs/spec.pdf)\n```\n",
+        ] {
+            assert_eq!(rewrite_asset_links(markdown, page, &dir), markdown);
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
 
-    format!("/static/{}", sanitize_filename(&relative_path))
+    #[test]
+    fn permalinks_with_dot_or_empty_segments_are_rejected() {
+        assert!(check_route_segments("/2024/short-slug/").is_ok());
+        assert!(check_route_segments("/").is_ok());
+        for bad in ["/../escape/", "/a/./b", "a//b", "/a/ /b"] {
+            let error = check_route_segments(bad).unwrap_err();
+            assert!(error.contains(bad), "{}", error);
+        }
+    }
+
+    #[test]
+    fn wiki_image_sizes() {
+        let page = Path::new("content/index.md");
+        assert_eq!(
+            process_alternative_images("![[/img/a.png|300]]", page),
+            r#"<img src="/img/a.png" alt="" width="300">"#
+        );
+        assert_eq!(
+            process_alternative_images("![[https://x.org/a.png|300x200]]", page),
+            r#"<img src="https://x.org/a.png" alt="" width="300" height="200">"#
+        );
+        assert_eq!(
+            process_alternative_images("![[/img/a.png|A \"cat\"|120]]", page),
+            r#"<img src="/img/a.png" alt="A&#x20;&quot;cat&quot;" width="120">"#
+        );
+    }
+
+    #[test]
+    fn non_numeric_wiki_image_options_stay_alt_text() {
+        let page = Path::new("content/index.md");
+        assert_eq!(process_alternative_images("![[/img/a.png|Route 66]]", page), "![Route 66](/img/a.png)");
+        assert_eq!(process_alternative_images("![[/img/a.png|300xabc]]", page), "![300xabc](/img/a.png)");
+        assert_eq!(parse_image_size("x200"), None);
+    }
+
+    #[test]
+    fn index_pages_get_their_directory_route() {
+        assert_eq!(default_route("index.md"), "/");
+        assert_eq!(default_route("blog/index.md"), "/blog");
+        assert_eq!(default_route("blog/first.md"), "/blog/first");
+    }
+
+    #[test]
+    fn wiki_and_standard_links_share_backlink_keys() {
+        let _lock = lock_global_state();
+        let target = PathBuf::from("content/notes/Target.md");
+        *FILE_CACHE.write().unwrap() = Some(HashMap::from([
+            ("Target".to_string(), vec![target.clone()]),
+            ("Target.md".to_string(), vec![target.clone()]),
+        ]));
+        let expected = route_key(&route_for(&target));
+
+        for post in ["See [[Target]] for more.", "Also [[Target.md|this]].", "Or [it](/notes/target#top)."] {
+            let snippets = link_snippets(&process_links(post));
+            let keys: Vec<Option<String>> = snippets.iter().map(|(url, _)| link_target_key(url)).collect();
+            assert_eq!(keys, [Some(expected.clone())], "{}", post);
+        }
+    }
+
+    #[test]
+    fn directories_publish_their_index_or_a_listing() {
+        let _lock = lock_global_state();
+        let dir = content_dir("directory-page");
+        fs::create_dir_all(dir.join("guide/advanced")).unwrap();
+        fs::write(dir.join("guide/index.md"), "---\ntitle: Guide\ndate: 2024-01-01\n---\n").unwrap();
+
+        let guide = dir.join("guide");
+        assert!(matches!(directory_page(&guide), DirectoryPage::Index(index) if index == guide.join("index.md")));
+        assert!(matches!(directory_page(&guide.join("advanced")), DirectoryPage::Listing));
+        assert!(matches!(directory_page(&dir.join("notes")), DirectoryPage::Listing));
+        assert_eq!(default_route("guide/advanced/index.md"), "/guide/advanced");
+        fs::remove_dir_all(dir).unwrap();
+    }
 }
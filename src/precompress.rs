@@ -0,0 +1,116 @@
+use crate::config::Compression;
+use crate::logger::debug;
+use brotli::CompressorWriter;
+use colored::Colorize;
+use flate2::write::GzEncoder;
+use std::error::Error;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// Extensions of the text outputs worth compressing; images and fonts are
+/// compressed already.
+const COMPRESSIBLE: [&str; 7] = ["html", "css", "js", "xml", "json", "svg", "txt"];
+/// Smaller files gain too little to be worth a second request path.
+const MIN_SIZE: u64 = 1024;
+
+/// What one format saved over the originals.
+#[derive(Debug)]
+pub struct Savings {
+    pub format: Compression,
+    pub files: usize,
+    pub bytes: u64,
+}
+
+/// Writes `<file>.gz` / `<file>.br` next to every compressible file in
+/// `out_dir` of at least `MIN_SIZE` bytes. A copy newer than its original
+/// (left by an earlier incremental build) is kept, and no copy is written
+/// when it wouldn't be smaller. Returns the compressed files, so they count
+/// as outputs, and the savings of each format.
+pub fn precompress(
+    out_dir: &Path,
+    formats: &[Compression],
+) -> Result<(Vec<PathBuf>, Vec<Savings>), Box<dyn Error>> {
+    let mut written = Vec::new();
+    let mut savings: Vec<Savings> = formats
+        .iter()
+        .map(|format| Savings {
+            format: *format,
+            files: 0,
+            bytes: 0,
+        })
+        .collect();
+    if formats.is_empty() {
+        return Ok((written, savings));
+    }
+
+    for entry in WalkDir::new(out_dir).sort_by_file_name().into_iter().filter_map(|e| e.ok()) {
+        let path = entry.path();
+        let extension = path.extension().and_then(|s| s.to_str()).unwrap_or_default();
+        if !entry.file_type().is_file() || !COMPRESSIBLE.contains(&extension) {
+            continue;
+        }
+        let metadata = entry.metadata()?;
+        if metadata.len() < MIN_SIZE {
+            continue;
+        }
+
+        let mut original = None;
+        for saved in &mut savings {
+            let format = saved.format;
+            let mut compressed_path = path.as_os_str().to_owned();
+            compressed_path.push(".");
+            compressed_path.push(format.extension());
+            let compressed_path = PathBuf::from(compressed_path);
+
+            let up_to_date = fs::metadata(&compressed_path)
+                .and_then(|compressed| Ok(compressed.modified()? >= metadata.modified()?))
+                .unwrap_or(false);
+            let compressed_len = if up_to_date {
+                fs::metadata(&compressed_path)?.len()
+            } else {
+                if original.is_none() {
+                    original = Some(fs::read(path)?);
+                }
+                let compressed = compress(original.as_deref().unwrap_or_default(), format)?;
+                if compressed.len() as u64 >= metadata.len() {
+                    if compressed_path.exists() {
+                        fs::remove_file(&compressed_path)?;
+                    }
+                    continue;
+                }
+                fs::write(&compressed_path, &compressed)?;
+                debug!(
+                    "{} {}",
+                    "Compressed".green(),
+                    compressed_path.display().to_string().replace('\\', "/").yellow()
+                );
+                compressed.len() as u64
+            };
+            saved.files += 1;
+            saved.bytes += metadata.len() - compressed_len;
+            written.push(compressed_path);
+        }
+    }
+    Ok((written, savings))
+}
+
+fn compress(data: &[u8], format: Compression) -> io::Result<Vec<u8>> {
+    match format {
+        Compression::Gzip => {
+            let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::best());
+            encoder.write_all(data)?;
+            encoder.finish()
+        }
+        Compression::Brotli => {
+            let mut compressed = Vec::new();
+            {
+                // Buffer size, quality (0-11) and window size (log2).
+                let mut writer = CompressorWriter::new(&mut compressed, 4096, 11, 22);
+                writer.write_all(data)?;
+            }
+            Ok(compressed)
+        }
+    }
+}
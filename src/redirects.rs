@@ -0,0 +1,186 @@
+use crate::{
+    logger::debug,
+    config::Config,
+    frontmatter::Frontmatter,
+    i18n::page_language,
+    file_ops::{create_directory_safely, safely_write_file},
+    paths::{normalize_route, route_key},
+};
+use colored::Colorize;
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+/// An old URL that should lead to a page's current route.
+#[derive(Debug, Clone)]
+pub struct Redirect {
+    pub from: String,
+    pub to: String,
+    /// Where the redirect was declared, for error messages.
+    pub origin: String,
+}
+
+/// A page's frontmatter `aliases` as redirects to `route`.
+pub fn frontmatter_aliases(frontmatter: &Frontmatter, route: &str, origin: &Path) -> Vec<Redirect> {
+    frontmatter
+        .aliases
+        .iter()
+        .map(|alias| Redirect {
+            from: normalize_route(alias),
+            to: route.to_string(),
+            origin: origin.display().to_string().replace('\\', "/"),
+        })
+        .collect()
+}
+
+/// Redirects listed under `[redirects]` in the configuration. Targets may be
+/// routes or full URLs.
+pub fn config_redirects(config: &Config) -> Vec<Redirect> {
+    config
+        .redirects
+        .iter()
+        .map(|(from, to)| Redirect {
+            from: normalize_route(from),
+            to: if to.contains("://") { to.clone() } else { normalize_route(to) },
+            origin: "[redirects] in the configuration".to_string(),
+        })
+        .collect()
+}
+
+/// Rejects redirects that would shadow a real page (`routes`), point outside
+/// the site or claim the same URL twice.
+pub fn validate_redirects(redirects: &[Redirect], routes: &HashSet<String>) -> Result<(), Box<dyn Error>> {
+    let mut claimed: HashMap<String, &Redirect> = HashMap::new();
+    for redirect in redirects {
+        if redirect.from == "/" || redirect.from.split('/').any(|segment| segment == "..") {
+            return Err(format!("Invalid alias '{}' in {}", redirect.from, redirect.origin).into());
+        }
+        let key = route_key(&redirect.from);
+        if routes.contains(&key) {
+            return Err(format!(
+                "Alias '{}' in {} collides with an existing page",
+                redirect.from, redirect.origin
+            )
+            .into());
+        }
+        if let Some(other) = claimed.insert(key, redirect) {
+            return Err(format!(
+                "Alias '{}' is claimed by both {} and {}",
+                redirect.from, other.origin, redirect.origin
+            )
+            .into());
+        }
+    }
+    Ok(())
+}
+
+/// Writes an `index.html` at each redirect's old URL that sends browsers on
+/// with a meta refresh, and returns the files written.
+pub fn write_alias_pages(
+    out_dir: &Path,
+    redirects: &[Redirect],
+    config: &Config,
+) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let base_path = config.general.base_path.trim_end_matches('/');
+    let mut written = Vec::new();
+    for redirect in redirects {
+        let target = if redirect.to.contains("://") {
+            redirect.to.clone()
+        } else {
+            format!("{}{}", base_path, redirect.to)
+        };
+        let canonical = config.site_url(&redirect.to);
+        // The language of the page redirected to; routes mirror `content/`.
+        let lang = page_language(Path::new(redirect.to.trim_start_matches('/')), &config.i18n.languages)
+            .map_or("en", |(lang, _)| lang);
+        let html = format!(
+            "<!DOCTYPE html>\n\
+             <html lang=\"{lang}\">\n\
+             <head>\n\
+             <meta charset=\"utf-8\">\n\
+             <title>Redirecting…</title>\n\
+             <link rel=\"canonical\" href=\"{canonical}\">\n\
+             <meta name=\"robots\" content=\"noindex\">\n\
+             <meta http-equiv=\"refresh\" content=\"0; url={target}\">\n\
+             </head>\n\
+             <body><p>This page has moved to <a href=\"{target}\">{target}</a>.</p></body>\n\
+             </html>\n",
+            canonical = htmlescape::encode_minimal(&canonical),
+            target = htmlescape::encode_minimal(&target),
+            lang = htmlescape::encode_attribute(lang),
+        );
+
+        let dir = redirect
+            .from
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .fold(out_dir.to_path_buf(), |dir, segment| dir.join(segment));
+        create_directory_safely(&dir)?;
+        let path = dir.join("index.html");
+        safely_write_file(&path, &html)?;
+        debug!(
+            "{} {} -> {}",
+            "Created alias".green(),
+            redirect.from.yellow(),
+            redirect.to.yellow()
+        );
+        written.push(path);
+    }
+    Ok(written)
+}
+
+/// Writes every redirect as a permanent one to a `_redirects` file in the
+/// `from to 301` format Netlify and Cloudflare Pages read.
+pub fn write_redirects_file(out_dir: &Path, redirects: &[Redirect]) -> Result<PathBuf, Box<dyn Error>> {
+    let lines: String = redirects
+        .iter()
+        .map(|redirect| format!("{} {} 301\n", redirect.from, redirect.to))
+        .collect();
+    let path = out_dir.join("_redirects");
+    safely_write_file(&path, &lines)?;
+    debug!("{} {}", "Generated _redirects with".green(), redirects.len());
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn redirect(from: &str, to: &str) -> Redirect {
+        Redirect {
+            from: from.to_string(),
+            to: to.to_string(),
+            origin: "content/post.md".to_string(),
+        }
+    }
+
+    #[test]
+    fn alias_stub_redirects_to_the_page_under_base_path() {
+        let mut config: Config = toml::from_str(include_str!("../Config.example.toml")).unwrap();
+        config.general.base_url = "https://example.com/docs".to_string();
+        config.general.base_path = "/docs".to_string();
+        let out_dir = std::env::temp_dir().join(format!("sekiei-aliases-{}", std::process::id()));
+
+        let written = write_alias_pages(&out_dir, &[redirect("/old/path", "/blog/new")], &config).unwrap();
+        let stub = out_dir.join("old").join("path").join("index.html");
+        assert_eq!(written, [stub.clone()]);
+        let html = fs::read_to_string(&stub).unwrap();
+        assert!(html.contains(r#"<meta http-equiv="refresh" content="0; url=/docs/blog/new">"#), "{}", html);
+        assert!(html.contains(r#"<link rel="canonical" href="https://example.com/docs/blog/new">"#), "{}", html);
+        fs::remove_dir_all(out_dir).unwrap();
+    }
+
+    #[test]
+    fn aliases_may_not_collide_with_pages_or_each_other() {
+        let routes = HashSet::from([route_key("/blog/new")]);
+        assert!(validate_redirects(&[redirect("/old", "/blog/new")], &routes).is_ok());
+
+        let error = validate_redirects(&[redirect("/Blog/New", "/x")], &routes).unwrap_err();
+        assert!(error.to_string().contains("collides with an existing page"), "{}", error);
+
+        let twice = [redirect("/old", "/a"), redirect("/OLD", "/b")];
+        let error = validate_redirects(&twice, &routes).unwrap_err();
+        assert!(error.to_string().contains("claimed by both"), "{}", error);
+    }
+}
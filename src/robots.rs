@@ -0,0 +1,27 @@
+use crate::{
+    logger::info,
+    config::Config,
+    file_ops::safely_write_file,
+};
+use colored::Colorize;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+/// Writes `robots.txt` at the site root. A `static/robots.txt` is copied
+/// as-is; otherwise `[seo] robots` is used, falling back to allowing every
+/// crawler.
+pub fn generate_robots(dist: &Path, config: &Config) -> Result<(), Box<dyn Error>> {
+    let custom = Path::new("static").join("robots.txt");
+    let contents = if custom.is_file() {
+        fs::read_to_string(&custom)?
+    } else if let Some(robots) = &config.seo.robots {
+        robots.clone()
+    } else {
+        "User-agent: *\nAllow: /\n".to_string()
+    };
+
+    safely_write_file(&dist.join("robots.txt"), &contents)?;
+    info!("{}", "Generated robots.txt".green());
+    Ok(())
+}
@@ -1,110 +1,147 @@
 use crate::{
+    logger::info,
     config::Config,
+    dates::parse_date,
     file_ops::safely_write_file,
-    lazy_load::add_lazy_loading,
-    markdown::{extract_frontmatter, markdown_to_html},
-    utils::is_not_hidden_dir,
+    images::use_webp_urls,
+    markdown::excerpt_html,
+    pages::Pages,
+    paths::route_for,
+    utils::ROOT_RELATIVE_URL,
 };
-use chrono::{DateTime, Utc, TimeZone};
-use rss::{ChannelBuilder, ItemBuilder};
+use rss::{ChannelBuilder, ItemBuilder, extension::dublincore::DublinCoreExtensionBuilder};
 use std::error::Error;
-use std::fs;
 use std::path::Path;
-use walkdir::WalkDir;
 use colored::Colorize;
 
-pub fn generate_rss(dist: &Path, config: &Config) -> Result<(), Box<dyn Error>> {
-    println!("{}", "Collecting posts for RSS...".blue());
+/// Writes `rss.xml` with every page the build renders (so without drafts
+/// or pages with invalid frontmatter).
+pub fn generate_rss(dist: &Path, config: &Config, pages: &Pages) -> Result<(), Box<dyn Error>> {
+    info!("{}", "Collecting posts for RSS...".blue());
 
     let mut posts = Vec::new();
-    for entry in WalkDir::new("content")
-        .into_iter()
-        .filter_entry(is_not_hidden_dir)
-        .filter_map(|e| e.ok())
-    {
-        if entry.path().is_file() && entry.path().extension().and_then(|s| s.to_str()) == Some("md")
-        {
-            let content = fs::read_to_string(entry.path())?;
-            let (frontmatter, md_content) = extract_frontmatter(&content)?;
-            let relative_path = entry
-                .path()
-                .strip_prefix("content")?
-                .to_string_lossy()
-                .replace('\\', "/");
-            let url = if relative_path == "index.md" {
-                "/".to_string()
-            } else {
-                format!("/{}", relative_path.replace(".md", ""))
-            };
-
-            let date_str = frontmatter["date"]
-                .as_str()
-                .ok_or("Missing date in frontmatter")?;
-            
-            let pub_date = parse_custom_date(date_str)
-                .map_err(|e| format!("Invalid date format in {}: {}", relative_path, e))?;
-
-            posts.push((
-                frontmatter,
-                md_content.to_string(),
-                url,
-                pub_date,
-                entry.path().to_path_buf(),
-            ));
-        }
+    for page in pages.iter() {
+        let relative_path = page
+            .path
+            .strip_prefix("content")?
+            .to_string_lossy()
+            .replace('\\', "/");
+        let url = route_for(&page.path);
+
+        let pub_date = parse_date(&page.frontmatter.date)
+            .map_err(|e| format!("Invalid date format in {}: {}", relative_path, e))?;
+
+        posts.push((page, url, pub_date));
     }
 
-    posts.sort_by(|a, b| b.3.cmp(&a.3));
+    posts.sort_by(|a, b| b.2.cmp(&a.2).then_with(|| a.1.cmp(&b.1)));
 
     let mut rss_items = Vec::new();
-    for (frontmatter, md_content, url, pub_date, path) in posts {
-        let title = frontmatter["title"]
-            .as_str()
-            .unwrap_or("Untitled")
-            .to_string();
-        let (html_content, _) = markdown_to_html(&md_content, &path);
-        let description = Some(add_lazy_loading(&html_content, config.images.compress_to_webp));
+    for (page, url, pub_date) in posts {
+        let frontmatter = &page.frontmatter;
+        let path = &page.path;
+        let title = frontmatter.title.clone();
+        let html_content = feed_html(&page.rendered(config).0, config);
+        let mut excerpt = excerpt_html(&page.markdown, &html_content, frontmatter.description.as_deref(), path, config);
+        if config.images.compress_to_webp {
+            excerpt = use_webp_urls(&excerpt);
+        }
+        let author = config
+            .page_author(frontmatter)
+            .map_err(|e| format!("Invalid author in {}: {}", path.display(), e))?;
+        // `<author>` needs an email address; `dc:creator` carries the name
+        // either way.
+        let dublin_core = author.as_ref().map(|author| {
+            DublinCoreExtensionBuilder::default()
+                .creators(vec![author.name.clone()])
+                .build()
+        });
 
         rss_items.push(
             ItemBuilder::default()
                 .title(Some(title))
-                .link(Some(format!("{}{}", config.general.base_url.clone(),url))) 
-                .description(description)
+                .link(Some(config.site_url(&url)))
+                .description(Some(absolutize_urls(&excerpt, config)))
+                .content(Some(absolutize_urls(&html_content, config)))
                 .pub_date(Some(pub_date.to_rfc2822()))
+                .author(author.as_ref().and_then(|author| author.rss_author()))
+                .dublin_core_ext(dublin_core)
                 .build(),
         );
     }
 
     let channel = ChannelBuilder::default()
         .title(config.general.title.clone())
-        .link(config.general.base_url.clone())
+        .link(config.site_url("/"))
         .description(config.general.description.clone()) 
         .items(rss_items)
         .build();
 
     let rss_xml = channel.to_string();
     safely_write_file(&dist.join("rss.xml"), &rss_xml)?;
-    println!(
+    info!(
         "{} {}",
-        "Generated RSS feed at".green(),
-        dist.join("rss.xml").display().to_string().yellow()
+        "Generated RSS feed".green(),
+        "rss.xml".yellow()
     );
 
     Ok(())
 }
 
-fn parse_custom_date(date_str: &str) -> Result<DateTime<Utc>, Box<dyn Error>> {
-    let formats = ["%d %b %Y", "%d %B %Y", "%Y-%m-%d", "%Y/%m/%d", "%d/%m/%Y"];
-    let trimmed_date = date_str.trim();
-    
-    for format in &formats {
-        if let Ok(naive_date) = chrono::NaiveDate::parse_from_str(trimmed_date, format) {
-            return Ok(Utc.from_utc_datetime(&naive_date.and_hms_opt(0, 0, 0).unwrap()));
-        }
+/// A page's HTML for its feed item, from its rendered markdown. Feed readers
+/// don't run the lazy-loading script, so images stay plain `<img src>` tags.
+fn feed_html(html_content: &str, config: &Config) -> String {
+    if config.images.compress_to_webp {
+        use_webp_urls(html_content)
+    } else {
+        html_content.to_string()
+    }
+}
+
+/// Feed readers can't resolve root-relative URLs, so rewrite `src`, `href`
+/// and `data-src` attributes starting with a single `/` against the site's
+/// URL, `base_path` included.
+fn absolutize_urls(html: &str, config: &Config) -> String {
+    ROOT_RELATIVE_URL
+        .replace_all(html, |caps: &regex::Captures| {
+            format!(r#"{}="{}""#, &caps[1], config.site_url(&caps[2]))
+        })
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::markdown::markdown_to_html;
+
+    #[test]
+    fn feed_images_are_plain_absolute_img_tags() {
+        let _lock = crate::utils::lock_global_state();
+        let mut config: Config = toml::from_str(include_str!("../Config.example.toml")).unwrap();
+        config.general.base_url = "https://example.com".to_string();
+        config.images.lazy_mode = crate::config::LazyMode::Placeholder;
+        let (html, _) = markdown_to_html("![Logo](/static/logo.png)", Path::new("content/post.md"), &config);
+        let html = feed_html(&html, &config);
+        let html = absolutize_urls(&html, &config);
+        assert!(html.contains(r#"<img src="https://example.com/static/logo."#), "{}", html);
+        assert!(!html.contains("data-src"));
+        assert!(!html.contains("lazy-image-container"));
+    }
+
+    #[test]
+    fn feed_urls_include_base_path() {
+        let mut config: Config = toml::from_str(include_str!("../Config.example.toml")).unwrap();
+        config.general.base_url = "https://example.com".to_string();
+        config.general.base_path = "/docs".to_string();
+        let html = absolutize_urls(r#"<a href="/docs/intro">Intro</a> <img src="/static/a.png">"#, &config);
+        assert_eq!(
+            html,
+            r#"<a href="https://example.com/docs/docs/intro">Intro</a> <img src="https://example.com/docs/static/a.png">"#
+        );
+        assert_eq!(config.site_url("/notes/a"), "https://example.com/docs/notes/a");
+
+        // A `base_url` that already ends with `base_path` isn't extended again.
+        config.general.base_url = "https://example.com/docs/".to_string();
+        assert_eq!(config.site_url("/notes/a"), "https://example.com/docs/notes/a");
     }
-    
-    Err(format!(
-        "Could not parse date '{}'. Expected format '24 Jan 2025' or '24 January 2025'",
-        trimmed_date
-    ).into())
-}
\ No newline at end of file
+}
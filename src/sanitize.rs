@@ -0,0 +1,347 @@
+use lazy_static::lazy_static;
+use pulldown_cmark::{Event, TagEnd};
+use regex::Regex;
+
+lazy_static! {
+    /// A comment, or a complete tag whose quoted attribute values may
+    /// contain `>`. Anything else starting with `<` is escaped as text.
+    static ref TAG_REGEX: Regex =
+        Regex::new(r#"<!--[\s\S]*?-->|<(/?)([a-zA-Z][a-zA-Z0-9-]*)((?:[^>"']|"[^"]*"|'[^']*')*)>"#).unwrap();
+    static ref ATTR_REGEX: Regex =
+        Regex::new(r#"([a-zA-Z_:][-a-zA-Z0-9_:.]*)(?:\s*=\s*("[^"]*"|'[^']*'|[^\s"'>]+))?"#).unwrap();
+}
+
+/// Elements whose contents are dropped along with the tags when they are not
+/// allowlisted, rather than being unwrapped to their text.
+const DROP_WITH_CONTENT: &[&str] = &["script", "style", "iframe", "object", "embed", "template"];
+
+const URL_ATTRIBUTES: &[&str] = &["href", "src", "action", "formaction", "xlink:href", "poster"];
+
+pub fn default_allowed_tags() -> Vec<String> {
+    [
+        "a", "abbr", "audio", "b", "blockquote", "br", "code", "dd", "del", "details", "div",
+        "dl", "dt", "em", "figcaption", "figure", "h1", "h2", "h3", "h4", "h5", "h6", "hr", "i",
+        "img", "ins", "kbd", "li", "mark", "ol", "p", "picture", "pre", "q", "s", "small",
+        "source", "span", "strong", "sub", "summary", "sup", "table", "tbody", "td", "tfoot",
+        "th", "thead", "tr", "u", "ul", "video",
+    ]
+    .iter()
+    .map(|tag| tag.to_string())
+    .collect()
+}
+
+/// Strips non-allowlisted tags, event-handler attributes and URLs with a
+/// scheme other than http(s) or mailto (e.g. `javascript:`) from raw HTML
+/// in markdown. Stateful so that an element opened in one `Event::Html`
+/// chunk and closed in a later one is still dropped whole.
+pub struct HtmlSanitizer<'a> {
+    allowed_tags: &'a [String],
+    skipping: Option<String>,
+}
+
+impl<'a> HtmlSanitizer<'a> {
+    pub fn new(allowed_tags: &'a [String]) -> Self {
+        HtmlSanitizer {
+            allowed_tags,
+            skipping: None,
+        }
+    }
+
+    fn is_allowed(&self, tag: &str) -> bool {
+        self.allowed_tags.iter().any(|allowed| allowed.eq_ignore_ascii_case(tag))
+    }
+
+    /// Sanitizes one chunk of raw HTML. A `<` that doesn't start a complete
+    /// tag, like an unterminated `<img src=x onerror=...`, is escaped so the
+    /// browser can't read a tag into it; comments are dropped.
+    pub fn sanitize(&mut self, html: &str) -> String {
+        let mut output = String::new();
+        let mut last_end = 0;
+
+        for caps in TAG_REGEX.captures_iter(html) {
+            let whole = caps.get(0).unwrap();
+            if self.skipping.is_none() {
+                output.push_str(&escape_stray_brackets(&html[last_end..whole.start()]));
+            }
+            last_end = whole.end();
+
+            let Some(name) = caps.get(2) else {
+                continue;
+            };
+            let is_closing = !caps[1].is_empty();
+            let name = name.as_str().to_lowercase();
+            let attrs = &caps[3];
+
+            if let Some(skipped) = &self.skipping {
+                if is_closing && *skipped == name {
+                    self.skipping = None;
+                }
+                continue;
+            }
+
+            if !self.is_allowed(&name) {
+                let self_closing = attrs.trim_end().ends_with('/');
+                if !is_closing && !self_closing && DROP_WITH_CONTENT.contains(&name.as_str()) {
+                    self.skipping = Some(name);
+                }
+                continue;
+            }
+
+            if is_closing {
+                output.push_str(&format!("</{}>", name));
+            } else {
+                output.push_str(&format!("<{}{}>", name, sanitize_attributes(attrs)));
+            }
+        }
+
+        if self.skipping.is_none() {
+            output.push_str(&escape_stray_brackets(&html[last_end..]));
+        }
+        output
+    }
+}
+
+fn escape_stray_brackets(text: &str) -> String {
+    text.replace('<', "&lt;")
+}
+
+/// Runs the raw HTML in `events` (`Event::Html` and `Event::InlineHtml`)
+/// through an `HtmlSanitizer`; other events pass through untouched.
+/// pulldown-cmark emits an HTML block one line per `Event::Html`, so a
+/// block's lines are sanitized together, and a tag whose attributes span
+/// several lines is seen whole.
+pub fn sanitize_events<'e>(
+    events: impl Iterator<Item = Event<'e>>,
+    allowed_tags: &[String],
+) -> Vec<Event<'e>> {
+    let mut sanitizer = HtmlSanitizer::new(allowed_tags);
+    let mut output = Vec::new();
+    let mut block = String::new();
+    for event in events {
+        match event {
+            Event::Html(html) => block.push_str(&html),
+            Event::InlineHtml(html) => output.push(Event::InlineHtml(sanitizer.sanitize(&html).into())),
+            Event::End(TagEnd::HtmlBlock) => {
+                output.push(Event::Html(sanitizer.sanitize(&block).into()));
+                block.clear();
+                output.push(event);
+            }
+            _ => output.push(event),
+        }
+    }
+    output
+}
+
+fn sanitize_attributes(attrs: &str) -> String {
+    let self_closing = attrs.trim_end().ends_with('/');
+    let mut output = String::new();
+
+    for caps in ATTR_REGEX.captures_iter(attrs.trim_end().trim_end_matches('/')) {
+        let name = caps[1].to_lowercase();
+        if name.starts_with("on") {
+            continue;
+        }
+        if let Some(value) = caps.get(2) {
+            if URL_ATTRIBUTES.contains(&name.as_str()) && is_unsafe_url(value.as_str()) {
+                continue;
+            }
+            output.push_str(&format!(" {}={}", &caps[1], value.as_str()));
+        } else {
+            output.push_str(&format!(" {}", &caps[1]));
+        }
+    }
+
+    if self_closing {
+        output.push_str(" /");
+    }
+    output
+}
+
+/// Schemes a URL attribute may use; anything else (`javascript:`,
+/// `vbscript:`, `data:`, ...) is dropped. URLs without a scheme are relative
+/// and always kept.
+const SAFE_SCHEMES: &[&str] = &["http", "https", "mailto"];
+
+/// Whether a URL attribute value would run script (or use a scheme not in
+/// `SAFE_SCHEMES`) once the browser has decoded it: entities are decoded and
+/// ASCII whitespace and control characters dropped before the scheme is read,
+/// as browsers do.
+fn is_unsafe_url(value: &str) -> bool {
+    let normalized: String = decode_entities(value.trim_matches(|c| c == '"' || c == '\''))
+        .chars()
+        .filter(|c| !c.is_ascii_whitespace() && !c.is_control())
+        .collect::<String>()
+        .to_lowercase();
+    let scheme_end = normalized.find([':', '/', '?', '#']);
+    match scheme_end {
+        Some(end) if normalized[end..].starts_with(':') => !SAFE_SCHEMES.contains(&&normalized[..end]),
+        _ => false,
+    }
+}
+
+/// Decodes numeric character references (with or without the trailing `;`)
+/// and the named ones that can hide a scheme.
+fn decode_entities(value: &str) -> String {
+    let mut decoded = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        match decode_entity(rest) {
+            Some((c, len)) => {
+                decoded.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+/// The character an entity at the start of `text` stands for and the
+/// entity's length.
+fn decode_entity(text: &str) -> Option<(char, usize)> {
+    if let Some(numeric) = text.strip_prefix("&#") {
+        let (digits, radix, prefix) = match numeric.strip_prefix(['x', 'X']) {
+            Some(hex) => (hex, 16, 3),
+            None => (numeric, 10, 2),
+        };
+        let len = digits.find(|c: char| !c.is_digit(radix)).unwrap_or(digits.len());
+        let code = u32::from_str_radix(&digits[..len], radix).ok()?;
+        let semicolon = usize::from(digits[len..].starts_with(';'));
+        return Some((char::from_u32(code).unwrap_or('\u{fffd}'), prefix + len + semicolon));
+    }
+    const NAMED: &[(&str, char)] = &[
+        ("&colon;", ':'),
+        ("&tab;", '\t'),
+        ("&newline;", '\n'),
+        ("&amp;", '&'),
+        ("&lt;", '<'),
+        ("&gt;", '>'),
+        ("&quot;", '"'),
+        ("&apos;", '\''),
+        ("&sol;", '/'),
+    ];
+    NAMED.iter().find_map(|(name, c)| {
+        text.get(..name.len())
+            .filter(|prefix| prefix.eq_ignore_ascii_case(name))
+            .map(|_| (*c, name.len()))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_safe_and_relative_urls() {
+        for url in [
+            "\"https://example.com\"",
+            "\"http://example.com/a?b=c\"",
+            "\"mailto:me@example.com\"",
+            "\"/notes/idea\"",
+            "\"../img/a.png\"",
+            "\"#section\"",
+            "\"page?next=a:b\"",
+        ] {
+            assert!(!is_unsafe_url(url), "{} should be kept", url);
+        }
+    }
+
+    #[test]
+    fn drops_script_and_unknown_schemes() {
+        for url in [
+            "\"javascript:alert(1)\"",
+            "'JavaScript:alert(1)'",
+            "\"vbscript:msgbox\"",
+            "\"data:text/html,<script>alert(1)</script>\"",
+        ] {
+            assert!(is_unsafe_url(url), "{} should be dropped", url);
+        }
+    }
+
+    #[test]
+    fn drops_encoded_script_urls() {
+        for url in [
+            "\"&#106;avascript:alert(1)\"",
+            "\"&#106avascript:alert(1)\"",
+            "\"&#x6A;avascript:alert(1)\"",
+            "\"java&#x09;script:alert(1)\"",
+            "\"java&#9;script:alert(1)\"",
+            "\"java&Tab;script:alert(1)\"",
+            "\"javascript&colon;alert(1)\"",
+            "\"java\tscript:alert(1)\"",
+            "\" \u{1}javascript:alert(1)\"",
+        ] {
+            assert!(is_unsafe_url(url), "{} should be dropped", url);
+        }
+    }
+
+    #[test]
+    fn sanitizer_removes_encoded_javascript_href() {
+        let allowed = default_allowed_tags();
+        let mut sanitizer = HtmlSanitizer::new(&allowed);
+        assert_eq!(
+            sanitizer.sanitize("<a href=\"&#106;avascript:alert(1)\">x</a>"),
+            "<a>x</a>"
+        );
+        assert_eq!(
+            sanitizer.sanitize("<a href=\"https://example.com\">x</a>"),
+            "<a href=\"https://example.com\">x</a>"
+        );
+    }
+
+    fn render(markdown: &str) -> String {
+        let allowed = default_allowed_tags();
+        let events = sanitize_events(pulldown_cmark::Parser::new(markdown), &allowed);
+        let mut html = String::new();
+        pulldown_cmark::html::push_html(&mut html, events.into_iter());
+        html
+    }
+
+    #[test]
+    fn unterminated_tags_are_escaped() {
+        let allowed = default_allowed_tags();
+        let mut sanitizer = HtmlSanitizer::new(&allowed);
+        assert_eq!(
+            sanitizer.sanitize("<img src=x onerror=alert(1)\n"),
+            "&lt;img src=x onerror=alert(1)\n"
+        );
+        assert_eq!(
+            sanitizer.sanitize("<b>bold</b> <img src=\"x onerror=alert(1)>"),
+            "<b>bold</b> &lt;img src=\"x onerror=alert(1)>"
+        );
+        // Read as a browser would: the next line's `</div` becomes attributes.
+        assert_eq!(
+            render("<div>\n<img src=x onerror=alert(1)\n</div>\n"),
+            "<div>\n<img src=x div>\n"
+        );
+    }
+
+    #[test]
+    fn attributes_split_over_newlines_are_sanitized() {
+        assert_eq!(
+            render("<div>\n<img\n src=\"a.png\"\n onerror=alert(1)>\n</div>\n"),
+            "<div>\n<img src=\"a.png\">\n</div>\n"
+        );
+        assert_eq!(
+            render("Look: <img\nsrc=\"a.png\"\nonerror=alert(1)> here\n"),
+            "<p>Look: <img src=\"a.png\"> here</p>\n"
+        );
+    }
+
+    #[test]
+    fn comments_are_dropped_and_quoted_brackets_kept() {
+        let allowed = default_allowed_tags();
+        let mut sanitizer = HtmlSanitizer::new(&allowed);
+        assert_eq!(sanitizer.sanitize("a<!-- <script>x</script> -->b"), "ab");
+        assert_eq!(
+            sanitizer.sanitize("<abbr title=\"a > b\">gt</abbr>"),
+            "<abbr title=\"a > b\">gt</abbr>"
+        );
+    }
+}
@@ -1,12 +1,202 @@
+use crate::logger::{error, info};
 use crate::build;
-use std::path::Path;
+use crate::config::Config;
+use crate::static_files::process_static_files;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime};
 use colored::Colorize;
+use walkdir::WalkDir;
 
-pub async fn serve() -> Result<(), Box<dyn std::error::Error>> {
-    let dist = Path::new("dist");
-    build::build().unwrap();
+/// How often the source tree is scanned for changes.
+const POLL_INTERVAL: Duration = Duration::from_millis(300);
+/// Changes are collected until the tree has been quiet this long, so saving
+/// several files (or one file several times) triggers a single rebuild.
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Where the certificate for `serve --tls` comes from.
+pub enum Tls {
+    /// A certificate for `localhost` generated at startup; browsers will warn
+    /// about it once.
+    SelfSigned,
+    /// PEM files given on the command line.
+    Files { cert: PathBuf, key: PathBuf },
+}
+
+pub async fn serve(config_path: PathBuf, tls: Option<Tls>) -> Result<(), Box<dyn std::error::Error>> {
+    let dist = PathBuf::from(Config::load(&config_path)?.paths.output_dir);
+    // Read the certificate before building so a bad path fails fast.
+    let pem = tls.as_ref().map(tls_pem).transpose()?;
+    build::build(&build::BuildOptions {
+        config_path: config_path.clone(),
+        ..Default::default()
+    })
+    .unwrap();
+    thread::spawn(move || watch(&config_path));
     let routes = warp::fs::dir(dist);
-    println!("{}", "Starting server at 8000".on_blue());
-    warp::serve(routes).run(([127, 0, 0, 1], 8000)).await;
+    let Some((cert, key)) = pem else {
+        info!("{}", "Starting server at 8000".on_blue());
+        warp::serve(routes).run(([127, 0, 0, 1], 8000)).await;
+        return Ok(());
+    };
+    let (_, server) = warp::serve(routes)
+        .tls()
+        .cert(cert)
+        .key(key)
+        .try_bind_with_graceful_shutdown(([127, 0, 0, 1], 8000), std::future::pending())
+        .map_err(|e| format!("Could not start HTTPS server: {}", e))?;
+    info!("{}", "Starting server at https://localhost:8000".on_blue());
+    server.await;
     Ok(())
 }
+
+/// The certificate and private key, as PEM.
+fn tls_pem(tls: &Tls) -> Result<(Vec<u8>, Vec<u8>), Box<dyn std::error::Error>> {
+    match tls {
+        Tls::SelfSigned => {
+            let rcgen::CertifiedKey { cert, key_pair } =
+                rcgen::generate_simple_self_signed(vec!["localhost".to_string(), "127.0.0.1".to_string()])?;
+            Ok((cert.pem().into_bytes(), key_pair.serialize_pem().into_bytes()))
+        }
+        Tls::Files { cert, key } => {
+            // warp only rejects a bad certificate on the first connection, so
+            // check at least that each file holds the right kind of PEM block.
+            let read = |path: &Path, label: &str| {
+                let pem = fs::read(path).map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+                if String::from_utf8_lossy(&pem).contains(&format!("{}-----", label)) {
+                    Ok(pem)
+                } else {
+                    Err(format!("{} does not contain a PEM {}", path.display(), label))
+                }
+            };
+            Ok((read(cert, "CERTIFICATE")?, read(key, "PRIVATE KEY")?))
+        }
+    }
+}
+
+/// The least work that brings `dist/` up to date with a set of changes.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Rebuild {
+    /// Only files under `static/` changed; recopy them.
+    Static,
+    /// Content or the public directory changed; re-render what the
+    /// incremental build finds stale (the edited pages, plus every page when
+    /// titles or links changed) and recopy public files.
+    Incremental,
+    /// The config or templates changed, which affects every output.
+    Full,
+}
+
+fn classify(path: &Path, config_path: &Path) -> Rebuild {
+    if path == config_path || path.starts_with("templates") {
+        Rebuild::Full
+    } else if path.starts_with("static") {
+        Rebuild::Static
+    } else {
+        Rebuild::Incremental
+    }
+}
+
+/// Modification times of every watched file.
+fn snapshot(config_path: &Path, public_dir: &str) -> HashMap<PathBuf, SystemTime> {
+    let mut files = HashMap::new();
+    let roots = [
+        Path::new("content"),
+        Path::new("static"),
+        Path::new("templates"),
+        config_path,
+        Path::new(public_dir),
+    ];
+    for root in roots {
+        for entry in WalkDir::new(root).into_iter().filter_map(|e| e.ok()) {
+            if let Ok(metadata) = entry.metadata()
+                && metadata.is_file()
+            {
+                files.insert(
+                    entry.path().to_path_buf(),
+                    metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+                );
+            }
+        }
+    }
+    files
+}
+
+fn changed_paths(
+    before: &HashMap<PathBuf, SystemTime>,
+    after: &HashMap<PathBuf, SystemTime>,
+) -> HashSet<PathBuf> {
+    let mut changed: HashSet<PathBuf> = after
+        .iter()
+        .filter(|(path, modified)| before.get(*path) != Some(*modified))
+        .map(|(path, _)| path.clone())
+        .collect();
+    changed.extend(before.keys().filter(|path| !after.contains_key(*path)).cloned());
+    changed
+}
+
+/// Polls the sources and rebuilds after each burst of changes.
+fn watch(config_path: &Path) {
+    let public_dir = Config::load(config_path)
+        .map(|config| config.paths.public_dir)
+        .unwrap_or_else(|_| "public".to_string());
+    let mut known = snapshot(config_path, &public_dir);
+    loop {
+        thread::sleep(POLL_INTERVAL);
+        let mut current = snapshot(config_path, &public_dir);
+        let mut changed = changed_paths(&known, &current);
+        if changed.is_empty() {
+            continue;
+        }
+        loop {
+            thread::sleep(DEBOUNCE);
+            let next = snapshot(config_path, &public_dir);
+            let more = changed_paths(&current, &next);
+            current = next;
+            if more.is_empty() {
+                break;
+            }
+            changed.extend(more);
+        }
+        known = current;
+
+        let rebuild = changed.iter().map(|path| classify(path, config_path)).max().unwrap_or(Rebuild::Incremental);
+        info!(
+            "{} {} changed file(s), {:?} rebuild",
+            "Detected".blue(),
+            changed.len(),
+            rebuild
+        );
+        if let Err(e) = run_rebuild(&rebuild, config_path) {
+            error!("{} {}", "Rebuild failed:".red(), e);
+        }
+    }
+}
+
+fn run_rebuild(rebuild: &Rebuild, config_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let full = build::BuildOptions {
+        config_path: config_path.to_path_buf(),
+        ..Default::default()
+    };
+    let incremental = build::BuildOptions {
+        incremental: true,
+        ..full.clone()
+    };
+    match rebuild {
+        Rebuild::Full => build::build(&full),
+        Rebuild::Incremental => build::build(&incremental),
+        Rebuild::Static => {
+            let config = Config::load(config_path)?;
+            if config.build.hash_static_files {
+                // Hashed names change with the contents; the new asset URLs
+                // change the site fingerprint, so every page is re-rendered
+                // to point at the new files.
+                return build::build(&incremental);
+            }
+            process_static_files(&Path::new(&config.paths.output_dir).join("static"), &config.build)?;
+            Ok(())
+        }
+    }
+}
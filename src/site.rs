@@ -0,0 +1,238 @@
+use crate::{config::NavScope, dates::parse_date, frontmatter::Frontmatter, paths::route_key, utils::slugify};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// Metadata for one content page, shared with every template as `site.pages`.
+#[derive(Debug, Serialize, Clone)]
+pub struct PageSummary {
+    pub title: String,
+    pub url: String,
+    pub date: String,
+    /// `updated` from the frontmatter, or `date` when it has none.
+    pub updated: String,
+    pub tags: Vec<String>,
+    pub description: Option<String>,
+    /// Directory of the source file relative to `content/` ("" for the root).
+    pub section: String,
+    pub series: Option<String>,
+    pub series_order: Option<i64>,
+    pub weight: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct NavLink {
+    pub title: String,
+    pub url: String,
+}
+
+impl From<&PageSummary> for NavLink {
+    fn from(page: &PageSummary) -> Self {
+        NavLink {
+            title: page.title.clone(),
+            url: page.url.clone(),
+        }
+    }
+}
+
+impl PageSummary {
+    pub fn from_frontmatter(frontmatter: &Frontmatter, url: String, section: String) -> Self {
+        PageSummary {
+            title: frontmatter.title.clone(),
+            url,
+            date: frontmatter.date.clone(),
+            updated: frontmatter.updated().to_string(),
+            tags: frontmatter.tags.clone(),
+            description: frontmatter.description.clone(),
+            section,
+            series: frontmatter.series.clone(),
+            series_order: frontmatter.series_order,
+            weight: frontmatter.weight,
+        }
+    }
+}
+
+/// Pages sharing a `series` frontmatter value.
+#[derive(Debug, Serialize, Clone)]
+pub struct Series {
+    pub name: String,
+    /// Overview page, `/series/<slug>`.
+    pub url: String,
+    /// Parts in reading order: by `series_order`, then the ones without it
+    /// oldest first.
+    pub pages: Vec<NavLink>,
+}
+
+/// A page's place in its series, exposed to content templates as `series`.
+#[derive(Debug, Serialize)]
+pub struct SeriesPosition<'a> {
+    pub name: &'a str,
+    pub url: &'a str,
+    pub pages: &'a [NavLink],
+    /// 1-based, for "Part 2 of 5".
+    pub position: usize,
+    pub total: usize,
+    pub prev: Option<&'a NavLink>,
+    pub next: Option<&'a NavLink>,
+}
+
+#[derive(Debug, Serialize, Default)]
+pub struct SiteContext {
+    pub pages: Vec<PageSummary>,
+    /// Every series, by name.
+    pub series: Vec<Series>,
+}
+
+impl SiteContext {
+    /// Sorts pages newest first; pages with unparseable dates go last.
+    pub fn sort_pages(&mut self) {
+        self.pages.sort_by(|a, b| {
+            let a_date = parse_date(&a.date).ok();
+            let b_date = parse_date(&b.date).ok();
+            match (a_date, b_date) {
+                (Some(a_date), Some(b_date)) => b_date.cmp(&a_date),
+                (Some(_), None) => std::cmp::Ordering::Less,
+                (None, Some(_)) => std::cmp::Ordering::Greater,
+                (None, None) => std::cmp::Ordering::Equal,
+            }
+            .then_with(|| a.title.cmp(&b.title))
+        });
+    }
+    /// Groups pages into `series`, each named after its first part's
+    /// `series` value and ordered for reading.
+    pub fn collect_series(&mut self) {
+        let mut groups: BTreeMap<&str, Vec<&PageSummary>> = BTreeMap::new();
+        for page in &self.pages {
+            if let Some(series) = page.series.as_deref() {
+                groups.entry(series).or_default().push(page);
+            }
+        }
+        self.series = groups
+            .into_iter()
+            .map(|(name, mut pages)| {
+                pages.sort_by(|a, b| {
+                    let a_key = (a.series_order.is_none(), a.series_order, parse_date(&a.date).ok());
+                    let b_key = (b.series_order.is_none(), b.series_order, parse_date(&b.date).ok());
+                    a_key.cmp(&b_key).then_with(|| a.title.cmp(&b.title))
+                });
+                Series {
+                    name: name.to_string(),
+                    url: format!("/series/{}", slugify(name)),
+                    pages: pages.into_iter().map(NavLink::from).collect(),
+                }
+            })
+            .collect();
+    }
+
+    /// Fails when a series overview would land on a page, directory or
+    /// alias in `routes` (route keys), or two series names share a slug.
+    pub fn validate_series(&self, routes: &HashSet<String>) -> Result<(), String> {
+        let mut claimed: HashMap<String, &str> = HashMap::new();
+        for series in &self.series {
+            let key = route_key(&series.url);
+            if routes.contains(&key) {
+                return Err(format!(
+                    "Overview page {} of series '{}' collides with an existing page",
+                    series.url, series.name
+                ));
+            }
+            if let Some(other) = claimed.insert(key, &series.name) {
+                return Err(format!(
+                    "Series '{}' and '{}' would share the overview page {}",
+                    other, series.name, series.url
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Where the page at `url` is in its series, if it is in one.
+    pub fn series_position(&self, url: &str) -> Option<SeriesPosition<'_>> {
+        self.series.iter().find_map(|series| {
+            let index = series.pages.iter().position(|page| page.url == url)?;
+            Some(SeriesPosition {
+                name: &series.name,
+                url: &series.url,
+                pages: &series.pages,
+                position: index + 1,
+                total: series.pages.len(),
+                prev: index.checked_sub(1).and_then(|i| series.pages.get(i)),
+                next: series.pages.get(index + 1),
+            })
+        })
+    }
+
+    /// Returns the chronologically previous (older) and next (newer) page
+    /// around `url`, optionally restricted to pages in the same section.
+    pub fn neighbors(&self, url: &str, scope: &NavScope) -> (Option<NavLink>, Option<NavLink>) {
+        let Some(current) = self.pages.iter().find(|page| page.url == url) else {
+            return (None, None);
+        };
+        let scoped: Vec<&PageSummary> = self
+            .pages
+            .iter()
+            .filter(|page| match scope {
+                NavScope::Site => true,
+                NavScope::Section => page.section == current.section,
+            })
+            .collect();
+        let Some(index) = scoped.iter().position(|page| page.url == url) else {
+            return (None, None);
+        };
+
+        let prev = scoped.get(index + 1).map(|page| NavLink::from(*page));
+        let next = index
+            .checked_sub(1)
+            .and_then(|i| scoped.get(i))
+            .map(|page| NavLink::from(*page));
+        (prev, next)
+    }
+}
+
+/// One step of the trail from the site root to the current page.
+#[derive(Debug, Serialize, Clone)]
+pub struct Breadcrumb {
+    pub name: String,
+    pub url: String,
+    /// Set on the last crumb, which templates usually render unlinked.
+    pub current: bool,
+}
+
+/// Builds the trail for a page in `section` (its directory relative to
+/// `content/`). Directories are named by their `index.md` title from
+/// `dir_titles` when there is one. `page` is the current page's title and
+/// URL; `None` makes the section itself the current crumb, as for listings
+/// and `index.md` pages.
+pub fn breadcrumbs(
+    section: &str,
+    page: Option<(&str, &str)>,
+    dir_titles: &HashMap<String, String>,
+) -> Vec<Breadcrumb> {
+    let mut crumbs = vec![Breadcrumb {
+        name: dir_titles.get("").cloned().unwrap_or_else(|| "Home".to_string()),
+        url: "/".to_string(),
+        current: false,
+    }];
+    let mut path = String::new();
+    for segment in section.split('/').filter(|segment| !segment.is_empty()) {
+        if !path.is_empty() {
+            path.push('/');
+        }
+        path.push_str(segment);
+        crumbs.push(Breadcrumb {
+            name: dir_titles.get(&path).cloned().unwrap_or_else(|| segment.to_string()),
+            url: format!("/{}", path),
+            current: false,
+        });
+    }
+    if let Some((title, url)) = page {
+        crumbs.push(Breadcrumb {
+            name: title.to_string(),
+            url: url.to_string(),
+            current: false,
+        });
+    }
+    if let Some(last) = crumbs.last_mut() {
+        last.current = true;
+    }
+    crumbs
+}
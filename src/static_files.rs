@@ -1,66 +1,182 @@
-use crate::file_ops::{create_directory_safely, safely_write_file};
+use crate::logger::{debug, info, warn};
+use crate::config::BuildConfig;
+use crate::svg::minify_svg;
+use crate::file_ops::{create_directory_safely, static_url, write_hashed_file};
+use std::collections::{HashMap, HashSet};
 use std::error::Error;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use walkdir::WalkDir;
 use css_minify::optimizations::{Level as CssLevel, Minifier as CssMinifier};
 use minify_js::{Session, TopLevelMode, minify as js_minify};
 use colored::Colorize;
 
-pub fn process_static_files(dist_static: &Path) -> Result<(), Box<dyn Error>> {
+/// Copies `static/` into `dist_static`, minifying CSS and JS (and SVG with
+/// `minify_svg`). Returns the URL of every file keyed by its path relative to
+/// `static/`; with `hash_static_files` the written files carry a content hash
+/// in their name. Pre-minified files are copied verbatim, `static_exclude`
+/// matches verbatim and unhashed, and a file that fails to minify is copied unchanged with a
+/// warning.
+pub fn process_static_files(dist_static: &Path, build: &BuildConfig) -> Result<HashMap<String, String>, Box<dyn Error>> {
+    let mut urls = HashMap::new();
     let static_dir = Path::new("static");
     if static_dir.exists() {
-        for entry in WalkDir::new(static_dir).into_iter().filter_map(|e| e.ok()) {
+        for entry in WalkDir::new(static_dir).sort_by_file_name().into_iter().filter_map(|e| e.ok()) {
             if entry.path().is_file() {
                 let relative_path = entry.path().strip_prefix(static_dir)?;
-                let output_path = dist_static.join(relative_path);
-                create_directory_safely(output_path.parent().unwrap())?;
+                let mut output_path = dist_static.join(relative_path);
+                let output_dir = output_path.parent().unwrap().to_path_buf();
+                create_directory_safely(&output_dir)?;
 
-                match entry.path().extension().and_then(|s| s.to_str()) {
+                let file_name = relative_path.file_name().unwrap_or_default().to_string_lossy();
+                let relative_name = relative_path.to_string_lossy().replace('\\', "/");
+                let skip_reason = build.skips_minification(&file_name, &relative_name);
+                let extension = entry.path().extension().and_then(|s| s.to_str());
+
+                let (content, action) = match extension.filter(|_| skip_reason.is_none()) {
                     Some("css") => {
-                        let css_content = fs::read_to_string(entry.path())?;
-                        let minified_css = CssMinifier::default()
-                            .minify(&css_content, CssLevel::Three).expect("Failed to minify CSS");
-                        safely_write_file(&output_path, &minified_css)?;
-                        println!(
-                            "{} {} -> {}",
-                            "Copying and minifying".green(),
-                            entry.path().display().to_string().yellow().replace('\\', "/").yellow(),
-                            output_path.display().to_string().yellow().replace('\\', "/").yellow()
-                        );
+                        let css_content = fs::read(entry.path())?;
+                        let minified = std::str::from_utf8(&css_content)
+                            .map_err(|e| e.to_string())
+                            .and_then(|css| {
+                                CssMinifier::default()
+                                    .minify(css, CssLevel::Three)
+                                    .map_err(|e| e.to_string())
+                            });
+                        minified_or_original(entry.path(), css_content, minified)
                     }
                     Some("js") => {
                         let js_content = fs::read(entry.path())?;
                         let mut minified_js = Vec::new();
                         let js_session = Session::new();
-                        js_minify(
+                        let result = js_minify(
                             &js_session,
                             TopLevelMode::Global,
                             &js_content,
                             &mut minified_js,
-                        ).expect("Failed to minify JS");
-                        fs::write(&output_path, &minified_js)?;
-                        println!(
-                            "{} {} -> {}",
-                            "Copying and minifying".green(),
-                            entry.path().display().to_string().yellow().replace('\\', "/").yellow(),
-                            output_path.display().to_string().yellow().replace('\\', "/").yellow()
-                        );
+                        ).map_err(|e| e.to_string());
+                        match result {
+                            Ok(()) => (minified_js, "Copying and minifying"),
+                            Err(e) => {
+                                warn_unminified(entry.path(), &e);
+                                (js_content, "Copying")
+                            }
+                        }
                     }
-                    _ => {
-                        fs::copy(entry.path(), &output_path)?;
-                        println!(
-                            "{} {} -> {}",
-                            "Copying".green(),
-                            entry.path().display().to_string().yellow().replace('\\', "/").yellow(),
-                            output_path.display().to_string().yellow().replace('\\', "/").yellow()
-                        );
+                    Some("svg") if build.minify_svg => {
+                        let svg_content = fs::read(entry.path())?;
+                        let minified = std::str::from_utf8(&svg_content)
+                            .map_err(|e| e.to_string())
+                            .and_then(minify_svg);
+                        minified_or_original(entry.path(), svg_content, minified)
                     }
+                    _ => (fs::read(entry.path())?, "Copying"),
+                };
+
+                if build.hash_static_files && !build.excludes_static(&file_name, &relative_name) {
+                    output_path = write_hashed_file(&output_dir, &file_name, &content)?;
+                } else {
+                    fs::write(&output_path, &content)?;
                 }
+                urls.insert(relative_name, static_url(dist_static, &output_path));
+
+                debug!(
+                    "{} {} -> {}{}",
+                    action.green(),
+                    entry.path().display().to_string().yellow().replace('\\', "/").yellow(),
+                    output_path.display().to_string().yellow().replace('\\', "/").yellow(),
+                    skip_reason.map(|reason| format!(" (not minified: {})", reason)).unwrap_or_default()
+                );
             }
         }
     } else {
-        println!("{}", "No static folder found, skipping static file copy.".yellow());
+        info!("{}", "No static folder found, skipping static file copy.".yellow());
+    }
+    Ok(urls)
+}
+
+/// Minifies an SVG file's contents, or warns and returns `None` when it
+/// isn't UTF-8 or can't be parsed, in which case it should be copied as is.
+pub fn minify_svg_or_warn(path: &Path, content: &[u8]) -> Option<String> {
+    let result = std::str::from_utf8(content)
+        .map_err(|e| e.to_string())
+        .and_then(minify_svg);
+    match result {
+        Ok(minified) => Some(minified),
+        Err(e) => {
+            warn_unminified(path, &e);
+            None
+        }
+    }
+}
+
+/// Picks the minified contents of a file, falling back (with a warning) to
+/// the original when minification failed.
+fn minified_or_original(
+    path: &Path,
+    original: Vec<u8>,
+    minified: Result<String, String>,
+) -> (Vec<u8>, &'static str) {
+    match minified {
+        Ok(minified) => (minified.into_bytes(), "Copying and minifying"),
+        Err(e) => {
+            warn_unminified(path, &e);
+            (original, "Copying")
+        }
+    }
+}
+
+fn warn_unminified(path: &Path, error: &str) {
+    warn!(
+        "{} {}: {} (copied unchanged)",
+        "Could not minify".yellow(),
+        path.display().to_string().replace('\\', "/"),
+        error
+    );
+}
+
+/// Copies `public_dir` to the root of `out_dir` byte for byte, keeping its
+/// directory layout, and returns the written paths. A file that would replace
+/// one generated by this build (a page, `robots.txt`, a feed) is skipped
+/// with a warning. `generated` holds this build's tracked outputs and
+/// `previous_outputs` the ones an incremental build found in `out_dir` from
+/// last time; other existing files were written by this build too.
+pub fn copy_public_files(
+    public_dir: &Path,
+    out_dir: &Path,
+    generated: &HashSet<PathBuf>,
+    previous_outputs: &[PathBuf],
+) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut written = Vec::new();
+    if !public_dir.is_dir() {
+        return Ok(written);
+    }
+    for entry in WalkDir::new(public_dir).sort_by_file_name().into_iter().filter_map(|e| e.ok()) {
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let relative_path = entry.path().strip_prefix(public_dir)?;
+        let output_path = out_dir.join(relative_path);
+        if generated.contains(&output_path)
+            || (output_path.exists() && !previous_outputs.contains(&output_path))
+        {
+            warn!(
+                "{} {} (dist/{} is generated by the build)",
+                "Skipping".yellow(),
+                entry.path().display().to_string().replace('\\', "/"),
+                relative_path.display().to_string().replace('\\', "/")
+            );
+            continue;
+        }
+        create_directory_safely(output_path.parent().unwrap())?;
+        fs::copy(entry.path(), &output_path)?;
+        debug!(
+            "{} {} -> {}",
+            "Copying".green(),
+            entry.path().display().to_string().replace('\\', "/").yellow(),
+            output_path.display().to_string().replace('\\', "/").yellow()
+        );
+        written.push(output_path);
     }
-    Ok(())
-}
\ No newline at end of file
+    Ok(written)
+}
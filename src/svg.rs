@@ -0,0 +1,167 @@
+/// Elements whose text content is significant and kept as written.
+const PRESERVE_TEXT: [&str; 5] = ["text", "tspan", "textPath", "style", "script"];
+
+/// Strips comments, `<metadata>` and editor bookkeeping (`sodipodi:` and
+/// `inkscape:` elements and attributes) from an SVG, drops whitespace between
+/// tags and collapses it inside attribute values. Fails on markup it can't
+/// follow, such as an unclosed tag or comment or mismatched elements, so
+/// callers can copy the file unchanged.
+pub fn minify_svg(svg: &str) -> Result<String, String> {
+    let mut output = String::with_capacity(svg.len());
+    let mut open: Vec<String> = Vec::new();
+    // Depth of the open element being dropped, if any; everything inside it
+    // is dropped too.
+    let mut dropping: Option<usize> = None;
+    let mut seen_svg = false;
+    let mut rest = svg.trim_start_matches('\u{feff}');
+
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix("<!--") {
+            let end = after.find("-->").ok_or("unclosed comment")?;
+            rest = &after[end + 3..];
+        } else if rest.starts_with("<![CDATA[") {
+            let end = rest.find("]]>").ok_or("unclosed CDATA section")? + 3;
+            if dropping.is_none() {
+                output.push_str(&rest[..end]);
+            }
+            rest = &rest[end..];
+        } else if rest.starts_with("<?") || rest.starts_with("<!") {
+            // A DOCTYPE may carry an internal subset with its own `<...>`.
+            let end = match (rest.find('['), rest.find('>')) {
+                (Some(open), Some(close)) if rest.starts_with("<!") && open < close => {
+                    rest.find("]>").ok_or("unclosed DOCTYPE")? + 2
+                }
+                (_, close) => close.ok_or("unclosed declaration")? + 1,
+            };
+            if dropping.is_none() {
+                output.push_str(&rest[..end]);
+            }
+            rest = &rest[end..];
+        } else if let Some(after) = rest.strip_prefix("</") {
+            let end = after.find('>').ok_or("unclosed end tag")?;
+            let name = after[..end].trim();
+            match open.pop() {
+                Some(expected) if expected == name => {}
+                Some(expected) => return Err(format!("</{}> closes <{}>", name, expected)),
+                None => return Err(format!("unexpected </{}>", name)),
+            }
+            if dropping.is_none() {
+                output.push_str("</");
+                output.push_str(name);
+                output.push('>');
+            } else if dropping == Some(open.len()) {
+                dropping = None;
+            }
+            rest = &after[end + 1..];
+        } else if rest.starts_with('<') {
+            let end = tag_end(rest).ok_or("unclosed tag")?;
+            let (name, attributes, self_closing) = parse_tag(&rest[1..end])?;
+            seen_svg |= name == "svg";
+            if dropping.is_none() && is_editor_element(&name) {
+                if !self_closing {
+                    dropping = Some(open.len());
+                }
+            } else if dropping.is_none() {
+                output.push('<');
+                output.push_str(&name);
+                for attribute in attributes.iter().filter(|a| !is_editor_attribute(a)) {
+                    output.push(' ');
+                    output.push_str(attribute);
+                }
+                output.push_str(if self_closing { "/>" } else { ">" });
+            }
+            if !self_closing {
+                open.push(name);
+            }
+            rest = &rest[end + 1..];
+        } else {
+            let end = rest.find('<').unwrap_or(rest.len());
+            let text = &rest[..end];
+            if dropping.is_none() {
+                if open.iter().any(|name| PRESERVE_TEXT.contains(&name.as_str())) {
+                    output.push_str(text);
+                } else if !text.trim().is_empty() {
+                    output.push_str(&text.split_whitespace().collect::<Vec<_>>().join(" "));
+                }
+            }
+            rest = &rest[end..];
+        }
+    }
+
+    if let Some(name) = open.pop() {
+        return Err(format!("unclosed <{}>", name));
+    }
+    if !seen_svg {
+        return Err("no <svg> element".to_string());
+    }
+    Ok(output)
+}
+
+/// Index of the `>` ending the tag at the start of `markup`, skipping any
+/// inside quoted attribute values.
+fn tag_end(markup: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in markup.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), _) if q == c => quote = None,
+            (None, '>') => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Splits the inside of a start tag into its name, its attributes (each
+/// `name="value"` with runs of whitespace in the value collapsed) and whether
+/// it is self-closing.
+fn parse_tag(inner: &str) -> Result<(String, Vec<String>, bool), String> {
+    let (inner, self_closing) = match inner.trim_end().strip_suffix('/') {
+        Some(inner) => (inner, true),
+        None => (inner, false),
+    };
+    let name_end = inner.find(char::is_whitespace).unwrap_or(inner.len());
+    let name = &inner[..name_end];
+    if name.is_empty() {
+        return Err("tag without a name".to_string());
+    }
+
+    let mut attributes = Vec::new();
+    let mut rest = inner[name_end..].trim_start();
+    while !rest.is_empty() {
+        let name_len = rest
+            .find(|c: char| c == '=' || c.is_whitespace())
+            .unwrap_or(rest.len());
+        let attribute_name = &rest[..name_len];
+        let after_name = rest[name_len..].trim_start();
+        let Some(value) = after_name.strip_prefix('=') else {
+            attributes.push(attribute_name.to_string());
+            rest = after_name;
+            continue;
+        };
+        let value = value.trim_start();
+        let quote = value
+            .chars()
+            .next()
+            .filter(|c| *c == '"' || *c == '\'')
+            .ok_or_else(|| format!("unquoted value for {}", attribute_name))?;
+        let value_end = value[1..]
+            .find(quote)
+            .ok_or_else(|| format!("unclosed value for {}", attribute_name))?
+            + 2;
+        let collapsed = value[1..value_end - 1].split_whitespace().collect::<Vec<_>>().join(" ");
+        attributes.push(format!("{}={}{}{}", attribute_name, quote, collapsed, quote));
+        rest = value[value_end..].trim_start();
+    }
+    Ok((name.to_string(), attributes, self_closing))
+}
+
+fn is_editor_element(name: &str) -> bool {
+    name == "metadata" || name.starts_with("sodipodi:") || name.starts_with("inkscape:")
+}
+
+fn is_editor_attribute(attribute: &str) -> bool {
+    ["sodipodi:", "inkscape:", "xmlns:sodipodi=", "xmlns:inkscape="]
+        .iter()
+        .any(|prefix| attribute.starts_with(prefix))
+}
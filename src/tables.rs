@@ -0,0 +1,128 @@
+use crate::logger::debug;
+use crate::file_ops::{static_url, write_hashed_file};
+use colored::Colorize;
+use css_minify::optimizations::{Level as CssLevel, Minifier as CssMinifier};
+use pulldown_cmark::{Alignment, Event, Tag, TagEnd};
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::Path;
+
+/// Writes the hashed table stylesheet and returns its URL keyed by unhashed
+/// file name.
+pub fn setup_tables(dist_static: &Path) -> Result<HashMap<String, String>, Box<dyn Error>> {
+    let tables_css = r#"
+.text-left { text-align: left; }
+.text-center { text-align: center; }
+.text-right { text-align: right; }
+
+.table-wrapper {
+    overflow-x: auto;
+    margin: 1rem 0;
+}
+
+.table-wrapper > table {
+    margin: 0;
+}
+"#;
+
+    let minified_css = CssMinifier::default().minify(tables_css, CssLevel::Three)?;
+    let css_path = write_hashed_file(dist_static, "tables.css", minified_css.as_bytes())?;
+
+    debug!("{}", "Generated and minified tables.css".green());
+    Ok(HashMap::from([(
+        "tables.css".to_string(),
+        static_url(dist_static, &css_path),
+    )]))
+}
+
+/// Wraps each table in a `<div class="table-wrapper">`, which scrolls
+/// sideways when the table is too wide, and writes table cells itself so
+/// column alignment becomes a `text-left`/`text-center`/`text-right` class
+/// instead of the inline `style` pulldown-cmark would emit.
+pub fn render_tables(events: Vec<Event>) -> Vec<Event> {
+    let mut output = Vec::with_capacity(events.len());
+    let mut alignments = Vec::new();
+    let mut in_head = false;
+    let mut column = 0;
+
+    for event in events {
+        match event {
+            Event::Start(Tag::Table(ref table_alignments)) => {
+                alignments = table_alignments.clone();
+                output.push(Event::Html("<div class=\"table-wrapper\">".into()));
+                output.push(event);
+            }
+            Event::End(TagEnd::Table) => {
+                output.push(event);
+                output.push(Event::Html("</div>\n".into()));
+            }
+            Event::Start(Tag::TableHead) => {
+                in_head = true;
+                column = 0;
+                output.push(event);
+            }
+            Event::End(TagEnd::TableHead) => {
+                in_head = false;
+                output.push(event);
+            }
+            Event::Start(Tag::TableRow) => {
+                column = 0;
+                output.push(event);
+            }
+            Event::Start(Tag::TableCell) => {
+                let class = match alignments.get(column) {
+                    Some(Alignment::Left) => " class=\"text-left\"",
+                    Some(Alignment::Center) => " class=\"text-center\"",
+                    Some(Alignment::Right) => " class=\"text-right\"",
+                    _ => "",
+                };
+                let cell = if in_head { "th" } else { "td" };
+                output.push(Event::InlineHtml(format!("<{}{}>", cell, class).into()));
+            }
+            Event::End(TagEnd::TableCell) => {
+                let cell = if in_head { "th" } else { "td" };
+                output.push(Event::InlineHtml(format!("</{}>", cell).into()));
+                column += 1;
+            }
+            _ => output.push(event),
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pulldown_cmark::{Options, Parser, html};
+
+    fn render(markdown: &str) -> String {
+        let events = render_tables(Parser::new_ext(markdown, Options::ENABLE_TABLES).collect());
+        let mut output = String::new();
+        html::push_html(&mut output, events.into_iter());
+        output
+    }
+
+    #[test]
+    fn alignment_becomes_a_class_on_every_cell() {
+        let html = render("| L | C | R | N |\n|:--|:-:|--:|---|\n| 1 | 2 | 3 | 4 |\n");
+        assert!(html.contains(r#"<th class="text-left">L</th>"#), "{}", html);
+        assert!(html.contains(r#"<th class="text-center">C</th>"#));
+        assert!(html.contains(r#"<th class="text-right">R</th>"#));
+        assert!(html.contains("<th>N</th>"));
+        assert!(html.contains(r#"<td class="text-left">1</td>"#));
+        assert!(html.contains(r#"<td class="text-center">2</td>"#));
+        assert!(html.contains(r#"<td class="text-right">3</td>"#));
+        assert!(html.contains("<td>4</td>"));
+        assert!(!html.contains("style="));
+    }
+
+    #[test]
+    fn each_table_is_wrapped_exactly_once() {
+        let table = "| a | b |\n|---|---|\n| 1 | 2 |\n";
+        let html = render(&format!("{}\ntext\n\n{}\n{}", table, table, table));
+        assert_eq!(html.matches("<table>").count(), 3);
+        assert_eq!(html.matches(r#"<div class="table-wrapper"><table>"#).count(), 3);
+        assert_eq!(html.matches("</table>\n</div>").count(), 3);
+        assert_eq!(html.matches("table-wrapper").count(), 3);
+    }
+}
@@ -1,86 +1,318 @@
-use crate::{config::{Config, ThemeType, get_preset_themes}, file_ops::safely_write_file};
+use crate::logger::debug;
+use crate::{config::{Config, ThemeType, get_preset_themes}, file_ops::{static_url, write_hashed_file}};
 use css_minify::optimizations::{Level as CssLevel, Minifier as CssMinifier};
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::collections::HashMap;
 use std::error::Error;
 use std::path::Path;
 use colored::Colorize;
 
-pub fn generate_theme_css(config: &Config, theme_css_path: &Path) -> Result<(), Box<dyn Error>> {
-    let required_vars = vec![
-        "background_color", "text_color", "link_color", "heading_color",
-        "code_background", "code_text", "border_color", "accent_color",
-        "blockquote_color", "secondary_background", "secondary_accent",
-        "highlight_add", "highlight_del", "highlight", "type", "constant",
-        "string", "comment", "keyword", "function", "variable", "punctuation",
-        "markup_heading", "diff_plus", "diff_minus", "attribute", "constructor",
-        "tag", "escape",
-    ];
-
-    let (light_vars, dark_vars) = match config.theme.theme_type {
+lazy_static! {
+    static ref HEX_COLOR_REGEX: Regex =
+        Regex::new(r"^#([0-9a-fA-F]{3}|[0-9a-fA-F]{4}|[0-9a-fA-F]{6}|[0-9a-fA-F]{8})$").unwrap();
+    static ref COLOR_FN_REGEX: Regex = Regex::new(r"^(rgba?|hsla?)\(([^()]*)\)$").unwrap();
+    static ref COLOR_COMPONENT_REGEX: Regex =
+        Regex::new(r"^[-+]?(\d+(\.\d*)?|\.\d+)(%|deg)?$").unwrap();
+}
+
+const NAMED_COLORS: &[&str] = &[
+    "aliceblue", "antiquewhite", "aqua", "aquamarine", "azure", "beige", "bisque", "black",
+    "blanchedalmond", "blue", "blueviolet", "brown", "burlywood", "cadetblue", "chartreuse",
+    "chocolate", "coral", "cornflowerblue", "cornsilk", "crimson", "cyan", "darkblue", "darkcyan",
+    "darkgoldenrod", "darkgray", "darkgreen", "darkgrey", "darkkhaki", "darkmagenta",
+    "darkolivegreen", "darkorange", "darkorchid", "darkred", "darksalmon", "darkseagreen",
+    "darkslateblue", "darkslategray", "darkslategrey", "darkturquoise", "darkviolet", "deeppink",
+    "deepskyblue", "dimgray", "dimgrey", "dodgerblue", "firebrick", "floralwhite", "forestgreen",
+    "fuchsia", "gainsboro", "ghostwhite", "gold", "goldenrod", "gray", "green", "greenyellow",
+    "grey", "honeydew", "hotpink", "indianred", "indigo", "ivory", "khaki", "lavender",
+    "lavenderblush", "lawngreen", "lemonchiffon", "lightblue", "lightcoral", "lightcyan",
+    "lightgoldenrodyellow", "lightgray", "lightgreen", "lightgrey", "lightpink", "lightsalmon",
+    "lightseagreen", "lightskyblue", "lightslategray", "lightslategrey", "lightsteelblue",
+    "lightyellow", "lime", "limegreen", "linen", "magenta", "maroon", "mediumaquamarine",
+    "mediumblue", "mediumorchid", "mediumpurple", "mediumseagreen", "mediumslateblue",
+    "mediumspringgreen", "mediumturquoise", "mediumvioletred", "midnightblue", "mintcream",
+    "mistyrose", "moccasin", "navajowhite", "navy", "oldlace", "olive", "olivedrab", "orange",
+    "orangered", "orchid", "palegoldenrod", "palegreen", "paleturquoise", "palevioletred",
+    "papayawhip", "peachpuff", "peru", "pink", "plum", "powderblue", "purple", "rebeccapurple",
+    "red", "rosybrown", "royalblue", "saddlebrown", "salmon", "sandybrown", "seagreen",
+    "seashell", "sienna", "silver", "skyblue", "slateblue", "slategray", "slategrey", "snow",
+    "springgreen", "steelblue", "tan", "teal", "thistle", "tomato", "turquoise", "violet",
+    "wheat", "white", "whitesmoke", "yellow", "yellowgreen", "transparent", "currentcolor",
+];
+
+/// Accepts hex (`#rgb`, `#rgba`, `#rrggbb`, `#rrggbbaa`), `rgb()`/`rgba()`,
+/// `hsl()`/`hsla()` and named colors.
+pub fn is_valid_css_color(value: &str) -> bool {
+    let value = value.trim();
+    if HEX_COLOR_REGEX.is_match(value) {
+        return true;
+    }
+    if let Some(captures) = COLOR_FN_REGEX.captures(&value.to_lowercase()) {
+        let components: Vec<&str> = captures[2]
+            .split(|c: char| c == ',' || c == '/' || c.is_whitespace())
+            .filter(|c| !c.is_empty())
+            .collect();
+        return (components.len() == 3 || components.len() == 4)
+            && components.iter().all(|c| COLOR_COMPONENT_REGEX.is_match(c));
+    }
+    NAMED_COLORS.contains(&value.to_lowercase().as_str())
+}
+
+fn validate_palette(theme_name: &str, vars: &HashMap<String, String>) -> Result<(), String> {
+    let mut keys: Vec<&String> = vars.keys().collect();
+    keys.sort();
+    for key in keys {
+        let value = &vars[key];
+        if !is_valid_css_color(value) {
+            return Err(format!(
+                "Invalid color for {} theme variable '{}': '{}'",
+                theme_name, key, value
+            ));
+        }
+    }
+    Ok(())
+}
+
+const UI_VARS: &[&str] = &[
+    "background_color", "text_color", "link_color", "heading_color",
+    "code_background", "code_text", "border_color", "accent_color",
+    "blockquote_color", "secondary_background", "secondary_accent",
+    "highlight_add", "highlight_del", "highlight",
+];
+
+const SYNTAX_VARS: &[&str] = &[
+    "type", "constant", "string", "comment", "keyword", "function", "variable",
+    "punctuation", "markup_heading", "diff_plus", "diff_minus", "attribute",
+    "constructor", "tag", "escape",
+];
+
+fn is_known_var(key: &str) -> bool {
+    UI_VARS.contains(&key) || SYNTAX_VARS.contains(&key)
+}
+
+fn apply_overrides(
+    vars: &mut HashMap<String, String>,
+    overrides: &HashMap<String, String>,
+    section: &str,
+) -> Result<(), String> {
+    let mut keys: Vec<&String> = overrides.keys().collect();
+    keys.sort();
+    for key in keys {
+        if !is_known_var(key) {
+            return Err(format!("Unknown theme variable '{}' in [theme] {}", key, section));
+        }
+        let value = &overrides[key];
+        if !is_valid_css_color(value) {
+            return Err(format!(
+                "Invalid color for '{}' in [theme] {}: '{}'",
+                key, section, value
+            ));
+        }
+        vars.insert(key.clone(), value.clone());
+    }
+    Ok(())
+}
+
+fn unknown_preset<T>(role: &str, name: &str, presets: &HashMap<String, T>) -> String {
+    let mut names: Vec<&str> = presets.keys().map(String::as_str).collect();
+    names.sort();
+    format!(
+        "Unknown preset '{}' for {} (available: {})",
+        name,
+        role,
+        names.join(", ")
+    )
+}
+
+/// Theme name and its variables, `light` and `dark` first.
+pub type Palettes = Vec<(String, HashMap<String, String>)>;
+
+/// Resolves the palettes `theme.css` is generated from (presets, custom
+/// palettes, syntax preset and overrides), checking that each one defines
+/// every variable with a valid color.
+pub fn resolve_palettes(config: &Config) -> Result<Palettes, String> {
+    let mut themes: Palettes = match config.theme.theme_type {
         ThemeType::Preset => {
-            let preset_name = config.theme.preset.as_ref().ok_or("Preset name not specified in Config.toml")?;
+            let light_name = config.theme.light_preset()
+                .ok_or("Light preset not specified in Config.toml (set 'preset' or 'preset_light')")?;
+            let dark_name = config.theme.dark_preset()
+                .ok_or("Dark preset not specified in Config.toml (set 'preset' or 'preset_dark')")?;
             let presets = get_preset_themes();
-            presets.get(preset_name)
-                .ok_or_else(|| format!("Unknown preset theme: {}", preset_name))?
-                .clone()
+            let (light, _) = presets.get(light_name)
+                .ok_or_else(|| unknown_preset("light palette", light_name, &presets))?;
+            let (_, dark) = presets.get(dark_name)
+                .ok_or_else(|| unknown_preset("dark palette", dark_name, &presets))?;
+            vec![
+                ("light".to_string(), light.clone()),
+                ("dark".to_string(), dark.clone()),
+            ]
         }
         ThemeType::Custom => {
-            let custom = config.theme.custom.as_ref().ok_or("Custom theme not specified in Config.toml")?;
-            (custom.light.clone(), custom.dark.clone())
+            let custom = config.theme.custom.as_ref().ok_or("theme_type = \"custom\" needs [theme.custom.light] and [theme.custom.dark] palettes in Config.toml")?;
+            for name in ["light", "dark"] {
+                if !custom.themes.contains_key(name) {
+                    return Err(format!("Custom theme is missing the '{}' palette", name));
+                }
+            }
+            custom.ordered_themes()
+                .into_iter()
+                .map(|(name, vars)| (name.clone(), vars.clone()))
+                .collect()
         }
     };
 
-    for var in &required_vars {
-        if !light_vars.contains_key(*var) {
-            return Err(format!("Missing light theme variable: {}", var).into());
-        }
-        if !dark_vars.contains_key(*var) {
-            return Err(format!("Missing dark theme variable: {}", var).into());
+    if let Some(syntax_name) = config.theme.syntax_preset.as_deref() {
+        let presets = get_preset_themes();
+        let (syntax_light, syntax_dark) = presets
+            .get(syntax_name)
+            .ok_or_else(|| unknown_preset("syntax_preset", syntax_name, &presets))?;
+        for (name, vars) in themes.iter_mut() {
+            let source = if name == "dark" { syntax_dark } else { syntax_light };
+            for var in SYNTAX_VARS {
+                if let Some(value) = source.get(*var) {
+                    vars.insert(var.to_string(), value.clone());
+                }
+            }
         }
     }
 
-    let mut light_css = String::new();
-    for (key, value) in &light_vars {
-        let css_key = format!("--{}", key.replace("_", "-"));
-        light_css.push_str(&format!("    {}: {};\n", css_key, value));
+    for (name, vars) in themes.iter_mut() {
+        match name.as_str() {
+            "light" => {
+                apply_overrides(vars, &config.theme.overrides, "overrides")?;
+                apply_overrides(vars, &config.theme.overrides_light, "overrides_light")?;
+            }
+            "dark" => {
+                apply_overrides(vars, &config.theme.overrides, "overrides")?;
+                apply_overrides(vars, &config.theme.overrides_dark, "overrides_dark")?;
+            }
+            _ => {}
+        }
     }
-    let mut dark_css = String::new();
-    for (key, value) in &dark_vars {
-        let css_key = format!("--{}", key.replace("_", "-"));
-        dark_css.push_str(&format!("    {}: {};\n", css_key, value));
+
+    for (name, vars) in &themes {
+        for var in UI_VARS {
+            if !vars.contains_key(*var) {
+                return Err(format!("Missing {} theme variable: {}", name, var));
+            }
+        }
+        for var in SYNTAX_VARS {
+            if !vars.contains_key(*var) {
+                return Err(format!("Missing {} syntax variable: {}", name, var));
+            }
+        }
+        validate_palette(name, vars)?;
     }
-    let theme_css = format!(
+    Ok(themes)
+}
+
+/// Writes the hashed `theme.css` into `dist_static` and returns its URL.
+pub fn generate_theme_css(config: &Config, dist_static: &Path) -> Result<String, Box<dyn Error>> {
+    let themes = resolve_palettes(config)?;
+    let minified_theme_css = theme_css(&themes)?;
+    let theme_css_path = write_hashed_file(dist_static, "theme.css", minified_theme_css.as_bytes())?;
+
+    debug!(
+        "{} theme.css with {} theme",
+        "Generated and minified".green(),
+        config.theme.theme_type.as_str().yellow()
+    );
+    Ok(static_url(dist_static, &theme_css_path))
+}
+
+/// Minified CSS defining every palette's variables: `light` on `:root`,
+/// `dark` under a dark system preference, and each palette (including
+/// those two) under its `[data-theme="..."]` selector.
+pub fn theme_css(themes: &Palettes) -> Result<String, String> {
+    let theme_blocks: Vec<(&str, String)> = themes
+        .iter()
+        .map(|(name, vars)| {
+            // Sorted so the file (and so its hashed name) only changes when
+            // a value does.
+            let mut keys: Vec<&String> = vars.keys().collect();
+            keys.sort();
+            let mut css = String::new();
+            for key in keys {
+                let value = &vars[key];
+                let css_key = format!("--{}", key.replace("_", "-"));
+                css.push_str(&format!("    {}: {};\n", css_key, value));
+            }
+            (name.as_str(), css)
+        })
+        .collect();
+    let vars_for = |theme: &str| {
+        theme_blocks
+            .iter()
+            .find(|(name, _)| *name == theme)
+            .map(|(_, css)| css.as_str())
+            .unwrap_or_default()
+    };
+
+    // Under a dark system preference, every explicitly chosen non-dark theme
+    // must still win over the dark palette.
+    let dark_exclusions: String = theme_blocks
+        .iter()
+        .filter(|(name, _)| *name != "dark")
+        .map(|(name, _)| format!(":not([data-theme=\"{}\"])", name))
+        .collect();
+
+    let mut theme_css = format!(
         r#"
 :root {{
 {light_css}
 }}
 
 @media (prefers-color-scheme: dark) {{
-    :root:not([data-theme="light"]) {{
+    :root{dark_exclusions} {{
 {dark_css}
     }}
 }}
-
-[data-theme="light"] {{
-{light_css}
-}}
-
-[data-theme="dark"] {{
-{dark_css}
-}}
 "#,
-        light_css = light_css,
-        dark_css = dark_css
+        light_css = vars_for("light"),
+        dark_css = vars_for("dark"),
+        dark_exclusions = dark_exclusions
     );
+    for (name, css) in &theme_blocks {
+        theme_css.push_str(&format!("\n[data-theme=\"{}\"] {{\n{}\n}}\n", name, css));
+    }
 
-    let minified_theme_css = CssMinifier::default()
+    CssMinifier::default()
         .minify(&theme_css, CssLevel::Three)
-        .map_err(|e| format!("Failed to minify theme.css: {}", e))?;
-    safely_write_file(theme_css_path, &minified_theme_css)?;
+        .map_err(|e| format!("Failed to minify theme.css: {}", e))
+}
 
-    println!(
-        "{} theme.css with {} theme",
-        "Generated and minified".green(),
-        config.theme.theme_type.as_str().yellow()
-    );
-    Ok(())
-}
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_preset_resolves_to_valid_colors() {
+        let mut presets: Vec<String> = get_preset_themes().into_keys().collect();
+        presets.sort();
+        let mut config: Config = toml::from_str(include_str!("../Config.example.toml")).unwrap();
+        config.theme.theme_type = ThemeType::Preset;
+        config.theme.preset_light = None;
+        config.theme.preset_dark = None;
+        config.theme.overrides.clear();
+        config.theme.overrides_light.clear();
+        config.theme.overrides_dark.clear();
+
+        for preset in &presets {
+            config.theme.preset = Some(preset.clone());
+            config.theme.syntax_preset = None;
+            let palettes = resolve_palettes(&config).unwrap_or_else(|e| panic!("preset '{}': {}", preset, e));
+            for (name, vars) in &palettes {
+                for (key, value) in vars {
+                    assert!(is_valid_css_color(value), "{} {} {}: '{}'", preset, name, key, value);
+                }
+            }
+            theme_css(&palettes).unwrap_or_else(|e| panic!("preset '{}': {}", preset, e));
+
+            // Each preset also works as the syntax colors of another.
+            config.theme.syntax_preset = Some(preset.clone());
+            config.theme.preset = presets.first().cloned();
+            resolve_palettes(&config).unwrap_or_else(|e| panic!("syntax_preset '{}': {}", preset, e));
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use crate::logger::info;
+use crate::config::get_preset_themes;
+use crate::file_ops::{create_directory_safely, safely_write_file};
+use crate::theme::theme_css;
+use colored::Colorize;
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::Path;
+
+/// Variables shown as swatches next to each preset name.
+const SWATCH_VARS: [&str; 5] = [
+    "background_color",
+    "text_color",
+    "link_color",
+    "heading_color",
+    "accent_color",
+];
+
+/// Prints every preset with swatches of its light and dark palettes, or with
+/// `show`, every variable of that one preset.
+pub fn list_themes(show: Option<&str>) -> Result<(), Box<dyn Error>> {
+    let presets = get_preset_themes();
+    let mut names: Vec<&String> = presets.keys().collect();
+    names.sort();
+
+    let Some(show) = show else {
+        let width = names.iter().map(|name| name.len()).max().unwrap_or(0);
+        for name in names {
+            let (light, dark) = &presets[name];
+            println!(
+                "{:width$}  {}  {}",
+                name,
+                swatches(light),
+                swatches(dark),
+                width = width
+            );
+        }
+        return Ok(());
+    };
+
+    let (light, dark) = presets.get(show).ok_or_else(|| unknown_preset(show, &names))?;
+    for (palette, vars) in [("light", light), ("dark", dark)] {
+        println!("{}", format!("[{}]", palette).bold());
+        let mut keys: Vec<&String> = vars.keys().collect();
+        keys.sort();
+        let width = keys.iter().map(|key| key.len()).max().unwrap_or(0);
+        for key in keys {
+            let value = &vars[key];
+            println!("  {:width$}  {}  {}", key, swatch(value), value, width = width);
+        }
+    }
+    Ok(())
+}
+
+/// Writes `theme-<name>.css` into `out_dir` for one preset, or for all of
+/// them, with the same CSS a build emits for that preset.
+pub fn export_themes(out_dir: &Path, name: Option<&str>) -> Result<(), Box<dyn Error>> {
+    let presets = get_preset_themes();
+    let mut names: Vec<&String> = presets.keys().collect();
+    names.sort();
+    if let Some(name) = name
+        && !presets.contains_key(name)
+    {
+        return Err(unknown_preset(name, &names).into());
+    }
+
+    create_directory_safely(out_dir)?;
+    for preset in names.into_iter().filter(|preset| name.is_none_or(|name| name == preset.as_str())) {
+        let (light, dark) = &presets[preset];
+        let css = theme_css(&vec![
+            ("light".to_string(), light.clone()),
+            ("dark".to_string(), dark.clone()),
+        ])?;
+        let path = out_dir.join(format!("theme-{}.css", preset));
+        safely_write_file(&path, &css)?;
+        info!(
+            "{} {}",
+            "Exported".green(),
+            path.display().to_string().replace('\\', "/").yellow()
+        );
+    }
+    Ok(())
+}
+
+fn unknown_preset(name: &str, names: &[&String]) -> String {
+    let names: Vec<&str> = names.iter().map(|name| name.as_str()).collect();
+    format!("Unknown preset '{}' (available: {})", name, names.join(", "))
+}
+
+fn swatches(vars: &HashMap<String, String>) -> String {
+    SWATCH_VARS
+        .iter()
+        .map(|var| vars.get(*var).map(|value| swatch(value)).unwrap_or_default())
+        .collect()
+}
+
+/// Two cells painted in `value` when it is a hex color; blank otherwise.
+fn swatch(value: &str) -> String {
+    match hex_rgb(value) {
+        Some((r, g, b)) => "  ".on_truecolor(r, g, b).to_string(),
+        None => "  ".to_string(),
+    }
+}
+
+/// `#rgb`/`#rrggbb` (alpha ignored) as RGB components.
+fn hex_rgb(value: &str) -> Option<(u8, u8, u8)> {
+    let hex = value.trim().strip_prefix('#')?;
+    let channel = |digits: &str| u8::from_str_radix(digits, 16).ok();
+    match hex.len() {
+        3 | 4 => {
+            let digit = |i: usize| channel(&hex[i..i + 1]).map(|d| d * 17);
+            Some((digit(0)?, digit(1)?, digit(2)?))
+        }
+        6 | 8 => Some((channel(&hex[0..2])?, channel(&hex[2..4])?, channel(&hex[4..6])?)),
+        _ => None,
+    }
+}
@@ -1,3 +1,18 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+
+lazy_static! {
+    /// `src`, `href` and `data-src` attributes holding a root-relative URL
+    /// (a single leading `/`).
+    pub static ref ROOT_RELATIVE_URL: Regex =
+        Regex::new(r#"\b(src|href|data-src)="(/[^/"][^"]*|/)""#).unwrap();
+}
+
+/// Flattens a content-relative path into a single file name for `static/`.
+///
+/// Separators become `-`, so a nested path also gets a hash of the original
+/// path before its extension; otherwise `a/b.png` would collide with
+/// `a-b.png`. Top-level file names are kept as they are.
 pub fn sanitize_filename(path: &str) -> String {
     let p = path.replace("/", "-").replace("\\", "-");
     let mut sanitized = String::new();
@@ -8,7 +23,61 @@ pub fn sanitize_filename(path: &str) -> String {
             sanitized.push_str(&format!("-u{:04x}", c as u32));
         }
     }
-    sanitized.replace('/', "-")
+    if !path.contains(['/', '\\']) {
+        return sanitized;
+    }
+    let hash = content_hash(path.replace('\\', "/").as_bytes());
+    match sanitized.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => format!("{}-{}.{}", stem, hash, extension),
+        _ => format!("{}-{}", sanitized, hash),
+    }
+}
+
+/// Heading-style slug: lowercased, spaces turned into `-`, and anything
+/// other than alphanumerics and `-` dropped.
+pub fn slugify(text: &str) -> String {
+    text.trim()
+        .to_lowercase()
+        .replace(' ', "-")
+        .replace(|c: char| !c.is_alphanumeric() && c != '-', "")
+}
+
+/// Short FNV-1a content hash, stable across builds and toolchains.
+pub fn content_hash(data: &[u8]) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in data {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("{:016x}", hash)[..8].to_string()
+}
+
+/// Joins a root-relative path onto `base_url`; absolute URLs are returned as-is.
+pub fn absolute_url(base_url: &str, path: &str) -> String {
+    if path.starts_with("http://") || path.starts_with("https://") {
+        return path.to_string();
+    }
+    format!(
+        "{}/{}",
+        base_url.trim_end_matches('/'),
+        path.trim_start_matches('/')
+    )
+}
+
+/// Prefixes root-relative URLs in rendered HTML with `base_path` so a site
+/// can be hosted under a subpath. Every URL is taken as relative to the
+/// site, so this runs exactly once, on a finished page; `/docs/intro` under
+/// a `/docs` base path becomes `/docs/docs/intro`.
+pub fn prefix_root_urls(html: &str, base_path: &str) -> String {
+    let base_path = base_path.trim_end_matches('/');
+    if base_path.is_empty() {
+        return html.to_string();
+    }
+    ROOT_RELATIVE_URL
+        .replace_all(html, |caps: &regex::Captures| {
+            format!(r#"{}="{}{}""#, &caps[1], base_path, &caps[2])
+        })
+        .to_string()
 }
 
 pub fn is_not_hidden_dir(entry: &walkdir::DirEntry) -> bool {
@@ -21,3 +90,38 @@ pub fn is_not_hidden_dir(entry: &walkdir::DirEntry) -> bool {
         true
     }
 }
+
+/// Serializes tests that depend on process-wide state: the working
+/// directory, which build tests switch to a temporary site, and the caches in
+/// `paths` that builds and link rewriting fill. A test that panicked while
+/// holding it doesn't fail the others.
+#[cfg(test)]
+pub(crate) fn lock_global_state() -> std::sync::MutexGuard<'static, ()> {
+    static LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+    LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nested_and_hyphenated_paths_get_distinct_names() {
+        let nested = sanitize_filename("a/b.png");
+        let flat = sanitize_filename("a-b.png");
+        assert_ne!(nested, flat);
+        assert_eq!(flat, "a-b.png");
+        assert!(nested.starts_with("a-b-") && nested.ends_with(".png"), "{}", nested);
+        assert_eq!(nested, sanitize_filename("a\\b.png"));
+    }
+
+    #[test]
+    fn root_relative_urls_are_prefixed_once_each() {
+        let html = r#"<a href="/docs/intro">Intro</a><a href="/">Home</a><img src="/static/a.png"><a href="https://example.com/x">x</a><a href="//cdn.example.com/y">y</a>"#;
+        assert_eq!(
+            prefix_root_urls(html, "/docs/"),
+            r#"<a href="/docs/docs/intro">Intro</a><a href="/docs/">Home</a><img src="/docs/static/a.png"><a href="https://example.com/x">x</a><a href="//cdn.example.com/y">y</a>"#
+        );
+        assert_eq!(prefix_root_urls(html, ""), html);
+    }
+}
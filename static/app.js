@@ -15,7 +15,15 @@ function setTheme(theme) {
     localStorage.setItem('theme', theme);
 }
 
+// Pages with `theme:` in their frontmatter keep that palette.
+function isThemeLocked() {
+    return document.documentElement.hasAttribute('data-theme-locked');
+}
+
 function toggleTheme() {
+    if (isThemeLocked()) {
+        return;
+    }
     const currentTheme = localStorage.getItem('theme') || 'light';
     const newTheme = currentTheme === 'dark' ? 'light' : 'dark';
     setTheme(newTheme);
@@ -26,6 +34,9 @@ function toggleTheme() {
 }
 
 function initializeTheme() {
+    if (isThemeLocked()) {
+        return;
+    }
     const savedTheme = localStorage.getItem('theme');
     if (savedTheme) {
         setTheme(savedTheme);